@@ -20,6 +20,56 @@ pub enum Error
     #[error(transparent)]
     #[cfg(feature="http")]
     HttpClientLegacyError(#[from] hyper_util::client::legacy::Error),
+    #[error(transparent)]
+    #[cfg(feature="http")]
+    TlsError(#[from] rustls::Error),
+    #[error("Превышено количество перенаправлений, последнее на `{0}`")]
+    #[cfg(feature="http")]
+    TooManyRedirects(String),
+    #[error("Циклическое перенаправление на `{0}`")]
+    #[cfg(feature="http")]
+    RedirectLoop(String),
+    #[error("Ошибка перенаправления `{0}`")]
+    #[cfg(feature="http")]
+    RedirectError(String),
+    #[error("Сервер `{url}` вернул статус {status}: {body}")]
+    #[cfg(feature="http")]
+    StatusError
+    {
+        url: String,
+        status: hyper::StatusCode,
+        ///начало тела ответа
+        body: String
+    },
+    #[error("Ответ `{url}` со статусом {status} не разобран как JSON: {error}; тело ответа: {body}")]
+    #[cfg(feature="http")]
+    JsonError
+    {
+        url: String,
+        status: hyper::StatusCode,
+        #[source]
+        error: serde_json::Error,
+        ///начало тела ответа
+        body: String
+    },
+    #[error("Размер загруженного файла `{0}` {1} байт не совпадает с Content-Length {2}")]
+    #[cfg(feature="http")]
+    DownloadSizeError(String, u64, u64),
+    #[error("Некорректный ответ на запрос части файла `{0}`")]
+    #[cfg(feature="http")]
+    RangeError(String),
+    #[error("Превышено время ожидания ответа от `{0}`")]
+    #[cfg(feature="http")]
+    Timeout(String),
+    #[error("Некорректная настройка клиента: {0}")]
+    #[cfg(feature="http")]
+    ConfigError(String),
+    #[error("Ошибка прокси: {0}")]
+    #[cfg(feature="http")]
+    ProxyError(String),
+    #[error("Запрос `{0}` отменен")]
+    #[cfg(feature="http")]
+    Cancelled(String),
     #[error("По данным параметрам заявки `{0}`")]
     NotFreeWorkers(String),
     #[error("Ошибка сервиса станций `{0}`")]
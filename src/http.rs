@@ -1,19 +1,135 @@
-use std::{any::TypeId, cell::LazyCell, net::{IpAddr, Ipv4Addr, SocketAddr}, result, sync::Arc, time::Duration};
-use http_body_util::Empty;
+use std::{any::TypeId, cell::LazyCell, net::{IpAddr, Ipv4Addr, SocketAddr}, path::Path, result, sync::Arc, time::Duration};
+use futures::{StreamExt, TryStreamExt};
 pub use http_body_util::{BodyExt, Full};
 use hyper::Method;
 pub use hyper::{body::Bytes, header::*, Request, Response, StatusCode, Uri};
-use hyper_util::{client::legacy::Client, rt::{TokioExecutor, TokioIo}};
+use hyper_util::{client::legacy::{connect::HttpConnector, Client}, rt::{TokioExecutor, TokioIo}};
 use tracing::debug;
 use rand::Rng;
 use std::collections::HashMap;
-use serde::Serialize;
-use tokio::{net::TcpSocket, sync::Mutex};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::{io::AsyncWriteExt, net::TcpSocket, sync::OnceCell};
 pub use tokio::net::TcpStream;
-//use rustls::RootCertStore;
-use hyper_rustls::ConfigBuilderExt;
 use crate::{error::Error, retry};
+mod auth;
+mod batch;
+mod body;
+mod browser;
+mod builder;
+mod cache;
+mod cookie;
+#[cfg(feature="decompression")]
+mod decompression;
+pub mod extract;
+mod middleware;
+mod multipart;
+mod proxy;
+mod rate_limit;
+mod redirect;
+mod response;
+mod retry_policy;
+mod server;
+#[cfg(any(test, feature="testing"))]
+pub mod testing;
+mod tls;
+pub use auth::{OAuth2ClientCredentials, TokenProvider};
+pub use batch::{BatchCancel, BatchExecutor};
+pub use browser::BrowserProfile;
+pub use builder::HyperClientBuilder;
+pub use cache::HttpCache;
+pub use cookie::{Cookie, CookieJar};
+pub use middleware::{Middleware, RequestId, RequestIdMiddleware, RequestParts, ResponseParts, TimingMiddleware, TracingMiddleware};
+pub use multipart::Multipart;
+pub use proxy::{Proxy, ProxyKind};
+pub use redirect::{RedirectAction, RedirectAttempt, RedirectPolicy};
+pub use retry_policy::RetryPolicy;
+pub use server::{CorsLayer, HttpServer, IntoResponse, PathParams, Router, ServerHandle};
+pub use tls::spki_sha256;
+use body::{ClientBody, RequestBody};
+use retry_policy::{RetryDecision, RetryResponse};
+pub use response::{BodyStream, ClientResponse, StreamingResponse};
 pub type BoxBody = http_body_util::combinators::BoxBody<Bytes, hyper::Error>;
+type HttpsClient = Client<hyper_rustls::HttpsConnector<proxy::ProxyConnector>, ClientBody>;
+
+///Добавление заголовков с заменой одноименных:
+///первое значение заменяет уже заданные, следующие значения того же имени добавляются к нему
+fn merge_headers<I: IntoIterator<Item = (HeaderName, HeaderValue)>>(target: &mut HeaderMap, headers: I)
+{
+    let mut replaced: Vec<HeaderName> = Vec::new();
+    for (name, value) in headers
+    {
+        if replaced.contains(&name)
+        {
+            target.append(name, value);
+        }
+        else
+        {
+            target.insert(name.clone(), value);
+            replaced.push(name);
+        }
+    }
+}
+
+///добавление `path` к пути `uri`, недопустимые в адресе символы кодируются
+fn join_path(uri: &Uri, path: &str) -> Result<Uri, Error>
+{
+    let mut joined = uri.to_string();
+    if !joined.ends_with('/')
+    {
+        joined.push('/');
+    }
+    for c in path.strip_prefix('/').unwrap_or(path).chars()
+    {
+        if c.is_ascii_graphic() && !matches!(c, '"' | '<' | '>' | '\\' | '^' | '`' | '{' | '|' | '}')
+        {
+            joined.push(c);
+        }
+        else
+        {
+            for b in c.encode_utf8(&mut [0; 4]).bytes()
+            {
+                joined.push_str(&format!("%{:02X}", b));
+            }
+        }
+    }
+    Ok(joined.parse().map_err(hyper::http::Error::from)?)
+}
+
+///ответ после всех перенаправлений, тело еще не прочитано
+struct RawResponse
+{
+    response: Response<hyper::body::Incoming>,
+    url: Uri,
+    redirects: Vec<Uri>,
+    ///место в ограничении одновременных запросов к хосту последнего перехода
+    permit: Option<tokio::sync::OwnedSemaphorePermit>,
+    ///окончание таймаута попытки, сдвинутое на время ожидания в ограничениях
+    deadline: tokio::time::Instant
+}
+
+impl RawResponse
+{
+    ///`decompress` - распаковать тело по Content-Encoding (фича `decompression`)
+    fn into_streaming(self, started: std::time::Instant, decompress: bool) -> StreamingResponse
+    {
+        let (mut parts, body) = self.response.into_parts();
+        let body: BodyStream = Box::pin(body.into_data_stream().map_err(Error::from));
+        #[cfg(feature="decompression")]
+        let body = if decompress { decompression::decompress(parts.status, &mut parts.headers, body) } else { body };
+        #[cfg(not(feature="decompression"))]
+        let _ = (decompress, &mut parts);
+        StreamingResponse
+        {
+            status: parts.status,
+            version: parts.version,
+            headers: parts.headers,
+            url: self.url,
+            redirects: self.redirects,
+            started,
+            body
+        }
+    }
+}
 
 async fn connect(addr: SocketAddr) -> Result<TcpStream, Error>
 {
@@ -162,135 +278,593 @@ pub fn unauthorized_response() -> Response<BoxBody>
     .unwrap()
 }
 
+///Выбор версии протокола HTTP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HttpVersionPolicy
+{
+    ///только HTTP/1.1
+    Http1Only,
+    ///HTTP/2 если сервер выбрал его через ALPN при подключении по TLS, иначе HTTP/1.1
+    #[default]
+    Negotiate,
+    ///только HTTP/2, без TLS используется h2c без предварительного Upgrade (prior knowledge)
+    Http2PriorKnowledge
+}
+
 #[derive(Debug, Clone)]
 pub struct HyperClient
 {
     uri: Uri,
-    headers: HashMap<HeaderName, String>,
-    timeout_from: u64,
-    timeout_to: u64,
-    retry_count: u8
+    headers: HeaderMap,
+    ///таймаут попытки, выбирается случайно из диапазона
+    read_timeout: (Duration, Duration),
+    connect_timeout: Option<Duration>,
+    ///ограничение времени запроса вместе со всеми повторами
+    total_timeout: Option<Duration>,
+    ///задержка перед повтором, выбирается случайно из диапазона
+    backoff: (Duration, Duration),
+    retry_count: u8,
+    pool_idle_timeout: Duration,
+    pool_max_idle_per_host: usize,
+    cookies: CookieJar,
+    redirect: RedirectPolicy,
+    retry: RetryPolicy,
+    tls: tls::TlsOptions,
+    proxy: proxy::ProxyOptions,
+    limiter: rate_limit::RateLimiter,
+    auth: auth::Auth,
+    middleware: middleware::MiddlewareStack,
+    cache: Option<HttpCache>,
+    browser: Option<browser::BrowserSession>,
+    http_version: HttpVersionPolicy,
+    #[cfg(feature="decompression")]
+    decompress: bool,
+    ///клиент с пулом соединений, создается при первом запросе и общий для всех клонов
+    client: Arc<OnceCell<HttpsClient>>
 }
 
 impl HyperClient
 {
+    #[deprecated(note = "используйте HyperClient::builder")]
     pub fn new(uri: Uri) -> Self
     {
-        let _ = rustls::crypto::ring::default_provider().install_default();
-
-        Self
-        { 
-            uri, 
-            headers: HashMap::new(),
-            timeout_from: 5000,
-            timeout_to: 30000,
-            retry_count: 7
-        }
+        Self::with_defaults(uri)
     }
-    ///выберется рандомное время из данного рэнджа
+    ///выберется рандомное время из данного рэнджа, используется и как таймаут попытки и как задержка между повторами
+    #[deprecated(note = "используйте HyperClient::builder, таймауты и задержка между повторами задаются раздельно")]
     pub fn new_with_timeout(uri: Uri, from: u64, to: u64, retry_count: u8) -> Self
+    {
+        let range = (Duration::from_millis(from), Duration::from_millis(to));
+        let mut client = Self::with_defaults(uri);
+        client.read_timeout = range;
+        client.backoff = range;
+        client.retry_count = retry_count;
+        client
+    }
+    ///таймаут попытки и задержка между повторами от 5 до 30 секунд, 7 попыток
+    fn with_defaults(uri: Uri) -> Self
     {
         let _ = rustls::crypto::ring::default_provider().install_default();
         Self
         { 
             uri, 
-            headers: HashMap::new(),
-            timeout_from: from,
-            timeout_to: to,
-            retry_count
+            headers: HeaderMap::new(),
+            read_timeout: (Duration::from_millis(5000), Duration::from_millis(30000)),
+            connect_timeout: None,
+            total_timeout: None,
+            backoff: (Duration::from_millis(5000), Duration::from_millis(30000)),
+            retry_count: 7,
+            pool_idle_timeout: Duration::from_secs(90),
+            pool_max_idle_per_host: usize::MAX,
+            cookies: CookieJar::new(),
+            redirect: RedirectPolicy::default(),
+            retry: RetryPolicy::default(),
+            tls: tls::TlsOptions::default(),
+            proxy: proxy::ProxyOptions::default(),
+            limiter: rate_limit::RateLimiter::default(),
+            auth: auth::Auth::default(),
+            middleware: middleware::MiddlewareStack::default(),
+            cache: None,
+            browser: None,
+            http_version: HttpVersionPolicy::default(),
+            #[cfg(feature="decompression")]
+            decompress: true,
+            client: Arc::new(OnceCell::new())
         }
     }
+    ///Настройка клиента с проверкой адреса, заголовков и таймаутов, ошибки настройки возвращаются из `HyperClientBuilder::build`
+    pub fn builder<U>(uri: U) -> HyperClientBuilder
+    where U: TryInto<Uri>, U::Error: std::fmt::Display
+    {
+        HyperClientBuilder::new(uri)
+    }
     pub fn get_uri(&self) -> &Uri
     {
         &self.uri
     }
-    pub fn with_path<P: AsRef<str>>(self, path: P) -> Self
+    ///добавление пути к адресу клиента, недопустимые в адресе символы кодируются,
+    ///если адрес все равно получается некорректным, он не меняется, а ошибка пишется в лог
+    ///(`HyperClientBuilder::with_path` возвращает такую ошибку из `build`)
+    pub fn with_path<P: AsRef<str>>(mut self, path: P) -> Self
     {
-        self.add_path(path.as_ref())
+        match join_path(&self.uri, path.as_ref())
+        {
+            Ok(uri) => self.uri = uri,
+            Err(e) => tracing::error!("Путь `{}` не добавлен к {}: {}", path.as_ref(), self.uri, e)
+        }
+        self
     }
+    ///заголовок с некорректным значением не добавляется, а ошибка пишется в лог
+    ///(`HyperClientBuilder::with_header` возвращает такую ошибку из `build`)
     pub fn with_header<S: AsRef<str> + ToString>(mut self, name: HeaderName, value: S) -> Self
     {
-        self.headers.insert(name, value.to_string());
+        match HeaderValue::from_str(value.as_ref())
+        {
+            Ok(v) => { self.headers.insert(name, v); },
+            Err(e) => tracing::error!("Некорректное значение заголовка {}: {}", name, e)
+        }
         self
     }
+    ///заголовки добавляются к уже заданным, одноименные заменяются,
+    ///несколько значений одного заголовка в `headers` сохраняются все
     pub fn with_headers<S: AsRef<str> + ToString>(mut self, headers: Vec<(HeaderName, S)>) -> Self
     {
-        self.headers = headers.into_iter().map(|m| (m.0, m.1.to_string())).collect::<HashMap<HeaderName, String>>();
+        let headers = headers.into_iter().filter_map(|(name, value)| match HeaderValue::from_str(value.as_ref())
+        {
+            Ok(v) => Some((name, v)),
+            Err(e) =>
+            {
+                tracing::error!("Некорректное значение заголовка {}: {}", name, e);
+                None
+            }
+        }).collect::<Vec<_>>();
+        merge_headers(&mut self.headers, headers);
+        self
+    }
+    ///хранилище cookie клиента, по умолчанию у каждого клиента свое
+    pub fn with_cookie_jar(mut self, jar: CookieJar) -> Self
+    {
+        self.cookies = jar;
+        self
+    }
+    pub fn get_cookie_jar(&self) -> &CookieJar
+    {
+        &self.cookies
+    }
+    ///правило выполнения перенаправлений, по умолчанию не более 10 перенаправлений
+    pub fn with_redirect_policy(mut self, policy: RedirectPolicy) -> Self
+    {
+        self.redirect = policy;
+        self
+    }
+    ///заголовок `Authorization: Basic` для всех запросов
+    pub fn with_basic_auth<U: AsRef<str>, P: AsRef<str>>(mut self, user: U, password: P) -> Self
+    {
+        match auth::Auth::basic(user.as_ref(), password.as_ref())
+        {
+            Ok(a) => self.auth = a,
+            Err(e) => tracing::error!("Авторизация не установлена: {}", e)
+        }
+        self
+    }
+    ///заголовок `Authorization: Bearer` для всех запросов, токен с некорректными символами не устанавливается, а ошибка пишется в лог
+    ///(`HyperClientBuilder::with_bearer_auth` возвращает такую ошибку из `build`)
+    pub fn with_bearer_auth<T: AsRef<str>>(mut self, token: T) -> Self
+    {
+        match auth::Auth::bearer(token.as_ref())
+        {
+            Ok(a) => self.auth = a,
+            Err(e) => tracing::error!("Авторизация не установлена: {}", e)
+        }
+        self
+    }
+    ///токен `Authorization: Bearer` запрашивается у `provider` перед каждой попыткой,
+    ///на ответ 401 токен запрашивается заново и запрос один раз повторяется
+    pub fn with_token_provider<T: TokenProvider + 'static>(mut self, provider: T) -> Self
+    {
+        self.auth = auth::Auth::Provider(Arc::new(provider));
+        self
+    }
+    ///добавление обработчика в конец цепочки `Middleware`, обработчики вызываются для каждой попытки запроса
+    pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self
+    {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+    ///кэш ответов на GET запросы, потоковые запросы и загрузка файлов выполняются без кэша
+    pub fn with_cache(mut self, cache: HttpCache) -> Self
+    {
+        self.cache = Some(cache);
+        self
+    }
+    ///заголовки браузера и Referer по предыдущей странице, история страниц общая для клонов клиента
+    pub fn with_browser_profile(mut self, profile: BrowserProfile) -> Self
+    {
+        self.browser = Some(browser::BrowserSession::new(profile));
+        self
+    }
+    ///правило повтора запросов, количество попыток задается `retry_count`
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self
+    {
+        self.retry = policy;
+        self
+    }
+    ///распаковка ответов сжатых gzip, deflate или br, по умолчанию включена
+    ///если заголовок Accept-Encoding не задан явно, он устанавливается в `gzip, deflate, br`,
+    ///при отключении тело возвращается как есть
+    #[cfg(feature="decompression")]
+    pub fn with_decompression(mut self, enabled: bool) -> Self
+    {
+        self.decompress = enabled;
+        self
+    }
+    #[cfg(feature="decompression")]
+    fn decompress_enabled(&self, method: &Method) -> bool
+    {
+        self.decompress && method != Method::HEAD
+    }
+    #[cfg(not(feature="decompression"))]
+    fn decompress_enabled(&self, _method: &Method) -> bool
+    {
+        false
+    }
+    ///сколько неиспользуемое соединение живет в пуле, по умолчанию 90 секунд
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self
+    {
+        self.pool_idle_timeout = timeout;
+        self.client = Arc::new(OnceCell::new());
         self
     }
-    fn add_path(mut self, path: &str) -> Self
+    ///максимальное количество неиспользуемых соединений в пуле для одного хоста, 0 - соединения не переиспользуются
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self
+    {
+        self.pool_max_idle_per_host = max;
+        self.client = Arc::new(OnceCell::new());
+        self
+    }
+    ///Повтор `f` пока `RetryPolicy` считает результат временной ошибкой, всего не более `retry_count` попыток (0 - без ограничения)
+    ///если попытки закончились, возвращается результат последней, в том числе ответ с ошибочным статусом
+    async fn with_retry<T, F, Fu>(&self, method: &Method, f: F) -> Result<T, Error>
+    where T: RetryResponse,
+          F: Fn() -> Fu,
+          Fu: std::future::Future<Output = Result<T, Error>>
+    {
+        let deadline = self.total_timeout.map(|t| tokio::time::Instant::now() + t);
+        let mut attempt: u32 = 1;
+        loop
+        {
+            let result = match deadline
+            {
+                Some(deadline) => match tokio::time::timeout_at(deadline, f()).await
+                {
+                    Ok(result) => result,
+                    Err(_) => return Err(Error::Timeout(self.uri.to_string()))
+                },
+                None => f().await
+            };
+            if self.retry_count != 0 && attempt >= self.retry_count as u32
+            {
+                return result;
+            }
+            let delay = match self.retry.decide(method, &result)
+            {
+                RetryDecision::Stop => return result,
+                RetryDecision::Retry => Self::rnd_duration(self.backoff),
+                RetryDecision::RetryAfter(delay) => delay
+            };
+            if deadline.is_some_and(|d| tokio::time::Instant::now() + delay >= d)
+            {
+                return result;
+            }
+            match &result
+            {
+                Ok(r) => tracing::warn!("Попытка {} запроса {} вернула статус {:?}, повтор через {:?}", attempt, self.uri, r.retry_status().map(|s| s.0), delay),
+                Err(e) => tracing::warn!("Попытка {} запроса {} завершилась ошибкой {}, повтор через {:?}", attempt, self.uri, e, delay)
+            }
+            attempt += 1;
+            tokio::time::sleep(delay).await;
+        }
+    }
+    ///Запрос с произвольным методом, ответ содержит заголовки, конечный адрес и цепочку перенаправлений
+    pub async fn request<S: AsRef<str> + ToString, B: Serialize + Clone>(&self, method: Method, params: &[(S, S)], body: Option<B>) -> Result<ClientResponse, Error>
+    {
+        let body = body.map(|b| RequestBody::json(&b)).transpose()?;
+        self.with_retry(&method, || self.get_body_timeout(params, &method, body.clone())).await
+    }
+    ///Запрос с произвольным методом, тело ответа не читается в память а доступно в виде потока
+    ///повтор запроса выполняется только до получения заголовков ответа
+    pub async fn request_stream<S: AsRef<str> + ToString, B: Serialize + Clone>(&self, method: Method, params: &[(S, S)], body: Option<B>) -> Result<StreamingResponse, Error>
+    {
+        let body = body.map(|b| RequestBody::json(&b)).transpose()?;
+        self.with_retry(&method, || self.get_stream_timeout(params, &method, body.clone())).await
+    }
+    ///Запрос JSON API: отправляется `Accept: application/json`, тело сериализуется в JSON с `Content-Type: application/json`,
+    ///для статуса 4xx и 5xx возвращается `Error::StatusError`, иначе тело ответа разбирается в `T`,
+    ///при ошибке разбора возвращается `Error::JsonError` со статусом и началом тела ответа
+    pub async fn request_json<S, B, T>(&self, method: Method, params: &[(S, S)], body: Option<&B>) -> Result<T, Error>
+    where S: AsRef<str> + ToString, B: Serialize + ?Sized, T: DeserializeOwned
+    {
+        let body = body.map(RequestBody::json).transpose()?;
+        let response = self.with_retry(&method, || async
+        {
+            let mut req = self.build_request(params, &method, body.clone())?;
+            req.headers_mut().entry(ACCEPT).or_insert(HeaderValue::from_static("application/json"));
+            self.send_timeout(req).await
+        }).await?;
+        response.error_for_status()?.json()
+    }
+    pub async fn get_json<T: DeserializeOwned>(&self) -> Result<T, Error>
+    {
+        self.request_json(Method::GET, &[] as &[(&str, &str)], None::<&()>).await
+    }
+    pub async fn get_json_with_params<S: AsRef<str> + ToString, T: DeserializeOwned>(&self, params: &[(S, S)]) -> Result<T, Error>
+    {
+        self.request_json(Method::GET, params, None::<&()>).await
+    }
+    pub async fn post_json<B: Serialize + ?Sized, T: DeserializeOwned>(&self, body: &B) -> Result<T, Error>
+    {
+        self.request_json(Method::POST, &[] as &[(&str, &str)], Some(body)).await
+    }
+    pub async fn put_json<B: Serialize + ?Sized, T: DeserializeOwned>(&self, body: &B) -> Result<T, Error>
+    {
+        self.request_json(Method::PUT, &[] as &[(&str, &str)], Some(body)).await
+    }
+    pub async fn patch_json<B: Serialize + ?Sized, T: DeserializeOwned>(&self, body: &B) -> Result<T, Error>
+    {
+        self.request_json(Method::PATCH, &[] as &[(&str, &str)], Some(body)).await
+    }
+    pub async fn delete_json<T: DeserializeOwned>(&self) -> Result<T, Error>
+    {
+        self.request_json(Method::DELETE, &[] as &[(&str, &str)], None::<&()>).await
+    }
+    ///Отправка формы `application/x-www-form-urlencoded`
+    pub async fn post_form<S: AsRef<str>>(&self, fields: &[(S, S)]) -> Result<ClientResponse, Error>
+    {
+        let body = RequestBody::form(fields);
+        self.with_retry(&Method::POST, || self.get_body_timeout(&[] as &[(&str, &str)], &Method::POST, Some(body.clone()))).await
+    }
+    ///Отправка формы `multipart/form-data`, файлы читаются с диска при каждой отправке
+    pub async fn post_multipart(&self, form: Multipart) -> Result<ClientResponse, Error>
+    {
+        let body = form.into_body().await?;
+        self.with_retry(&Method::POST, || self.get_body_timeout(&[] as &[(&str, &str)], &Method::POST, Some(body.clone()))).await
+    }
+    ///GET запросы по адресам `uris` с настройками клиента, не более `concurrency` одновременно,
+    ///результаты в порядке адресов, срок и отмена пакета задаются через `BatchExecutor`
+    pub async fn fetch_all<I: IntoIterator<Item = Uri>>(&self, uris: I, concurrency: usize) -> Vec<Result<ClientResponse, Error>>
     {
-        let mut uri = self.uri.to_string();
-        if !uri.ends_with("/")
+        BatchExecutor::new(concurrency).fetch_all(self, uris).await
+    }
+    pub async fn get_stream(&self) -> Result<StreamingResponse, Error>
+    {
+        self.request_stream(Method::GET, &[] as &[(&str, &str)], None::<bool>).await
+    }
+    ///Загрузка файла по адресу клиента в `path`
+    ///файл пишется во временный `path.part` и переименовывается только после успешной загрузки и сверки с Content-Length,
+    ///`progress` вызывается после каждой записанной части с количеством загруженных байт и размером из Content-Length
+    pub async fn download_to<P: AsRef<Path>, F: FnMut(u64, Option<u64>) + Send>(&self, path: P, progress: F) -> Result<u64, Error>
+    {
+        self.download(path.as_ref(), progress, false).await
+    }
+    ///Загрузка файла с докачкой, то же что `download_to`, но при обрыве загрузки `path.part` не удаляется,
+    ///а следующая попытка (в том числе при следующем вызове) запрашивает только недостающую часть через `Range` с проверкой `If-Range`,
+    ///если сервер не поддерживает диапазоны (нет `Accept-Ranges: bytes`, ETag или Last-Modified) файл загружается заново
+    pub async fn download_to_resumable<P: AsRef<Path>, F: FnMut(u64, Option<u64>) + Send>(&self, path: P, progress: F) -> Result<u64, Error>
+    {
+        self.download(path.as_ref(), progress, true).await
+    }
+    async fn download<F: FnMut(u64, Option<u64>) + Send>(&self, path: &Path, progress: F, resumable: bool) -> Result<u64, Error>
+    {
+        let part = crate::io::part_file_path(path);
+        let validator = Self::validator_path(&part);
+        let progress = std::sync::Mutex::new(progress);
+        let result = self.with_retry(&Method::GET, || self.download_attempt(&part, &progress, resumable)).await;
+        match result
+        {
+            Ok(size) =>
+            {
+                //переименование в пределах одной файловой системы атомарно
+                tokio::fs::rename(&part, path).await?;
+                let _ = tokio::fs::remove_file(&validator).await;
+                Ok(size)
+            },
+            Err(e) =>
+            {
+                //недокачанный файл оставляем для следующей попытки, если ошибка не связана с ответом сервера
+                if !resumable || matches!(e, Error::StatusError { .. })
+                {
+                    let _ = tokio::fs::remove_file(&part).await;
+                    let _ = tokio::fs::remove_file(&validator).await;
+                }
+                Err(e)
+            }
+        }
+    }
+    ///файл с ETag или Last-Modified для проверки недокачанного файла
+    fn validator_path(part: &Path) -> std::path::PathBuf
+    {
+        let mut name = part.as_os_str().to_owned();
+        name.push(".validator");
+        std::path::PathBuf::from(name)
+    }
+    async fn download_attempt<F: FnMut(u64, Option<u64>)>(&self, part: &Path, progress: &std::sync::Mutex<F>, resumable: bool) -> Result<u64, Error>
+    {
+        let timeout = Self::rnd_duration(self.read_timeout);
+        let validator_path = Self::validator_path(part);
+        let mut req = self.build_request(&[] as &[(&str, &str)], &Method::GET, None)?;
+        let mut offset: u64 = 0;
+        if resumable
+        {
+            let existing = tokio::fs::metadata(part).await.map(|m| m.len()).unwrap_or_default();
+            let validator = tokio::fs::read_to_string(&validator_path).await.ok().and_then(|v| HeaderValue::from_str(&v).ok());
+            if let (true, Some(validator)) = (existing > 0, validator)
+            {
+                offset = existing;
+                debug!("Докачка {} с {} байта", part.display(), offset);
+                req.headers_mut().insert(RANGE, HeaderValue::from_str(&format!("bytes={}-", offset)).unwrap());
+                req.headers_mut().insert(IF_RANGE, validator);
+            }
+            //диапазоны считаются по телу без сжатия
+            req.headers_mut().insert(ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+        }
+        let response = self.send_stream_timeout(req).await?;
+        let status = response.status();
+        let (mut file, mut written, total) = if offset > 0 && status == StatusCode::PARTIAL_CONTENT
         {
-            uri.push('/');
+            let (start, total) = Self::content_range(response.headers())
+                .ok_or_else(|| Error::RangeError(format!("Content-Range отсутствует в ответе {}", response.url())))?;
+            if start != offset
+            {
+                let _ = tokio::fs::remove_file(part).await;
+                return Err(Error::RangeError(format!("запрошено с {} байта, получено с {}", offset, start)));
+            }
+            let file = tokio::fs::OpenOptions::new().append(true).open(part).await?;
+            (file, offset, total)
+        }
+        else if offset > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE
+        {
+            //файл уже загружен полностью, иначе недокачанный файл не соответствует файлу на сервере
+            let total = Self::content_range(response.headers()).and_then(|(_, t)| t);
+            if total == Some(offset)
+            {
+                return Ok(offset);
+            }
+            let _ = tokio::fs::remove_file(part).await;
+            return Err(Error::RangeError(format!("диапазон с {} байта не принят сервером", offset)));
         }
-        if path.starts_with("/")
+        else if status.is_success()
         {
-            self.uri = [&uri, &path[1..]].concat().parse().unwrap();
+            if resumable
+            {
+                match Self::range_validator(response.headers())
+                {
+                    Some(v) => tokio::fs::write(&validator_path, v).await?,
+                    None => { let _ = tokio::fs::remove_file(&validator_path).await; }
+                }
+            }
+            let total = response.content_length();
+            (tokio::fs::File::create(part).await?, 0, total)
         }
         else
         {
-            self.uri = [&uri, path].concat().parse().unwrap();
+            return Err(response.collect().await?.status_error());
+        };
+        let mut stream = response.bytes_stream();
+        //таймаут действует на ожидание каждой следующей части, а не на всю загрузку
+        loop
+        {
+            let chunk = match tokio::time::timeout(timeout, stream.next()).await
+            {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(_) => Err(Error::Timeout(self.uri.to_string()))
+            };
+            let chunk = match chunk
+            {
+                Ok(c) => c,
+                Err(e) =>
+                {
+                    file.flush().await?;
+                    return Err(e);
+                }
+            };
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+            (progress.lock().unwrap())(written, total);
         }
-        self
+        file.flush().await?;
+        if let Some(total) = total
+        {
+            if total != written
+            {
+                return Err(Error::DownloadSizeError(part.display().to_string(), written, total));
+            }
+        }
+        Ok(written)
+    }
+    ///начало диапазона и полный размер из `Content-Range: bytes 100-199/200` или `bytes */200`
+    fn content_range(headers: &HeaderMap) -> Option<(u64, Option<u64>)>
+    {
+        let value = headers.get(CONTENT_RANGE)?.to_str().ok()?.strip_prefix("bytes ")?;
+        let (range, total) = value.split_once('/')?;
+        let total = total.trim().parse().ok();
+        let start = match range.split_once('-')
+        {
+            Some((start, _)) => start.trim().parse().ok()?,
+            None => 0
+        };
+        Some((start, total))
     }
-    pub async fn get_with_params<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Result<(StatusCode, Bytes), Error>
+    ///сильный ETag или Last-Modified если сервер поддерживает запрос диапазонов
+    fn range_validator(headers: &HeaderMap) -> Option<String>
+    {
+        let accept_ranges = headers.get(ACCEPT_RANGES).and_then(|v| v.to_str().ok());
+        if !accept_ranges.is_some_and(|v| v.split(',').any(|u| u.trim().eq_ignore_ascii_case("bytes")))
+        {
+            return None;
+        }
+        headers.get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.starts_with("W/"))
+            .or_else(|| headers.get(LAST_MODIFIED).and_then(|v| v.to_str().ok()))
+            .map(|v| v.to_owned())
+    }
+    pub async fn get_with_params<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Result<ClientResponse, Error>
     {
         self.get_body_retry(params, Method::GET, None::<bool>).await
     }
-    pub async fn get(&self) -> Result<(StatusCode, Bytes), Error>
+    pub async fn get(&self) -> Result<ClientResponse, Error>
     {
         let params: Vec<(String, String)> = Vec::new();
         self.get_body_retry(&params, Method::GET, None::<bool>).await
     }
-    pub async fn get_with_body<B: Serialize + Clone>(&self, body: B) -> Result<(StatusCode, Bytes), Error>
+    pub async fn get_with_body<B: Serialize + Clone>(&self, body: B) -> Result<ClientResponse, Error>
     {
         let v: Vec<(&str, &str)> = Vec::new();
         self.get_body_retry(&v, Method::GET, Some(body)).await
     }
-    pub async fn post_with_params<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Result<(StatusCode, Bytes), Error>
+    pub async fn post_with_params<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Result<ClientResponse, Error>
     {
         self.get_body_retry(params, Method::POST, None::<bool>).await
     }
-    pub async fn post_with_body<B: Serialize + Clone>(&self, body: B) -> Result<(StatusCode, Bytes), Error>
+    pub async fn post_with_body<B: Serialize + Clone>(&self, body: B) -> Result<ClientResponse, Error>
     {
         let v: Vec<(&str, &str)> = Vec::new();
         self.get_body_retry(&v, Method::POST, Some(body)).await
     }
-    pub async fn patch_with_params<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Result<(StatusCode, Bytes), Error>
+    pub async fn patch_with_params<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Result<ClientResponse, Error>
     {
         self.get_body_retry(params, Method::PATCH, None::<bool>).await
     }
-    pub async fn patch_with_body<B: Serialize + Clone>(&self, body: B) -> Result<(StatusCode, Bytes), Error>
+    pub async fn patch_with_body<B: Serialize + Clone>(&self, body: B) -> Result<ClientResponse, Error>
     {
         let v: Vec<(&str, &str)> = Vec::new();
         self.get_body_retry(&v, Method::PATCH, Some(body)).await
     }
-    pub async fn put_with_params<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Result<(StatusCode, Bytes), Error>
+    pub async fn put_with_params<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Result<ClientResponse, Error>
     {
         self.get_body_retry(params, Method::PUT, None::<bool>).await
     }
-    pub async fn put_with_body<B: Serialize + Clone>(&self, body: B) -> Result<(StatusCode, Bytes), Error>
+    pub async fn put_with_body<B: Serialize + Clone>(&self, body: B) -> Result<ClientResponse, Error>
     {
         let v: Vec<(&str, &str)> = Vec::new();
         self.get_body_retry(&v, Method::PUT, Some(body)).await
     }
-    pub async fn delete<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Result<(StatusCode, Bytes), Error>
+    pub async fn delete<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Result<ClientResponse, Error>
     {
         self.get_body_retry(params, Method::DELETE, None::<bool>).await
     }
-    pub async fn delete_with_body<S: AsRef<str> + ToString, B: Serialize + Clone>(&self, body: B) -> Result<(StatusCode, Bytes), Error>
+    pub async fn delete_with_body<S: AsRef<str> + ToString, B: Serialize + Clone>(&self, body: B) -> Result<ClientResponse, Error>
     {
         let v: Vec<(&str, &str)> = Vec::new();
         self.get_body_retry(&v, Method::DELETE, Some(body)).await
     }
-    fn apply_params_to_uri<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Uri
+    fn apply_params_to_uri<S: AsRef<str> + ToString>(&self, params: &[(S, S)]) -> Result<Uri, Error>
     {
         let params_len = params.len();
         if params_len == 0
         {
-            self.uri.clone()
+            Ok(self.uri.clone())
         }
         else
         {
@@ -310,135 +884,688 @@ impl HyperClient
                     uri.push('&');
                 }
             }
-            uri.parse().unwrap()
+            Ok(uri.parse().map_err(hyper::http::Error::from)?)
         }
     }
 
-    async fn get_body_timeout<S: AsRef<str> + ToString>(&self, params: &[(S, S)], method: &Method, body: Option<Bytes>)  -> Result<(StatusCode, Bytes), Error>
+    ///Content-Type тела JSON не заменяет заданный в заголовках клиента, у формы и multipart - заменяет
+    fn build_request<S: AsRef<str> + ToString>(&self, params: &[(S, S)], method: &Method, body: Option<RequestBody>) -> Result<Request<RequestBody>, Error>
     {
+        let uri = self.apply_params_to_uri(params)?;
+        debug!("Запрос URI: {}", &uri);
         let mut req =  Request::builder()
         .method(method)
-        .uri(self.apply_params_to_uri(params));
-        debug!("Запрос URI: {}", self.apply_params_to_uri(params));
+        .uri(&uri);
         let headers = req.headers_mut().unwrap();
-        for (k, v) in &self.headers
+        headers.extend(self.headers.clone());
+        if let Some(c) = self.cookies.cookie_header(&uri)
         {
-            headers.insert(k, v.parse().unwrap());
+            headers.insert(COOKIE, c);
         }
-        let guard = COOK.lock().await;
-        if let Some(c) = guard.get(self.get_uri().host().unwrap())
+        if let Some(browser) = &self.browser
         {
-            headers.insert(COOKIE, c.parse().unwrap());
+            browser.apply(&uri, headers);
         }
-        drop(guard);
-        let body = if let Some(b) = body
+        #[cfg(feature="decompression")]
+        if self.decompress && !headers.contains_key(ACCEPT_ENCODING)
         {
-            to_body(b.clone().into())
+            headers.insert(ACCEPT_ENCODING, HeaderValue::from_static(decompression::ACCEPT_ENCODING_VALUE));
         }
-        else
+        let body = body.unwrap_or_default();
+        if let Some(content_type) = &body.content_type
         {
-            to_body(Bytes::new())
-        };
-        let req: Request<http_body_util::combinators::BoxBody<Bytes, hyper::Error>> = req
-        .body(body)
-        .unwrap();
-        let response = match tokio::time::timeout(Self::rnd_duration(self.timeout_from, self.timeout_to),  Self::get_body_tls(req)).await
+            if body.replaces_content_type
+            {
+                headers.insert(CONTENT_TYPE, content_type.clone());
+            }
+            else
+            {
+                headers.entry(CONTENT_TYPE).or_insert(content_type.clone());
+            }
+            //файлы передаются потоком, размер тела указывается явно
+            headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        }
+        Ok(req.body(body)?)
+    }
+
+    async fn get_body_timeout<S: AsRef<str> + ToString>(&self, params: &[(S, S)], method: &Method, body: Option<RequestBody>)  -> Result<ClientResponse, Error>
+    {
+        let req = self.build_request(params, method, body)?;
+        self.send_timeout(req).await
+    }
+    async fn send_timeout(&self, req: Request<RequestBody>)  -> Result<ClientResponse, Error>
+    {
+        let navigation = req.method() == Method::GET;
+        let response = match &self.cache
         {
-            Ok(response) => response,
-            Err(_) => Err(Error::SendError("Connection timeout".to_owned()))
+            Some(cache) =>
+            {
+                let authorized = req.headers().contains_key(AUTHORIZATION) || !matches!(self.auth, auth::Auth::None);
+                cache.send(req, authorized, |req| self.send_uncached(req)).await?
+            },
+            None => self.send_uncached(req).await?
         };
-        response
+        if let Some(browser) = self.browser.as_ref().filter(|_| navigation)
+        {
+            browser.navigated(&response);
+        }
+        Ok(response)
+    }
+    async fn send_uncached(&self, req: Request<RequestBody>)  -> Result<ClientResponse, Error>
+    {
+        let started = std::time::Instant::now();
+        let decompress = self.decompress_enabled(req.method());
+        let mut response = self.get_body_tls(req, Self::rnd_duration(self.read_timeout)).await?;
+        //место в ограничении одновременных запросов занято до получения тела
+        let _permit = response.permit.take();
+        let deadline = response.deadline;
+        self.until(deadline, response.into_streaming(started, decompress).collect()).await
+    }
+    async fn get_stream_timeout<S: AsRef<str> + ToString>(&self, params: &[(S, S)], method: &Method, body: Option<RequestBody>)  -> Result<StreamingResponse, Error>
+    {
+        let req = self.build_request(params, method, body)?;
+        self.send_stream_timeout(req).await
+    }
+    ///таймаут действует только на получение заголовков ответа
+    async fn send_stream_timeout(&self, req: Request<RequestBody>)  -> Result<StreamingResponse, Error>
+    {
+        let started = std::time::Instant::now();
+        let decompress = self.decompress_enabled(req.method());
+        Ok(self.get_body_tls(req, Self::rnd_duration(self.read_timeout)).await?.into_streaming(started, decompress))
     }
 
-    fn rnd_duration(timeout_from: u64, timeout_to: u64) -> Duration
+    fn rnd_duration((from, to): (Duration, Duration)) -> Duration
+    {
+        if from >= to
+        {
+            return from;
+        }
+        rand::rng().random_range(from..to)
+    }
+    async fn get_body_retry<S: AsRef<str> + ToString, B: Serialize + Clone>(&self, params: &[(S, S)], method: Method, body: Option<B>) -> Result<ClientResponse, Error>
     {
-        Duration::from_millis(rand::rng().random_range(timeout_from..timeout_to))
+        self.request(method, params, body).await
     }
-    async fn get_body_retry<S: AsRef<str> + ToString, B: Serialize + Clone>(&self, params: &[(S, S)], method: Method, body: Option<B>) -> Result<(StatusCode, Bytes), Error>
+
+    ///копия запроса для отправки, исходный запрос остается для повторной отправки,
+    ///запросу по http через HTTP прокси добавляется авторизация на прокси
+    fn clone_request(&self, req: &Request<RequestBody>) -> Request<ClientBody>
     {
-        let body: Option<Bytes> = body.and_then(|b| Some(Bytes::from(serde_json::to_string(&b).unwrap())));
-        retry::retry(self.retry_count, self.timeout_from, self.timeout_to, || self.get_body_timeout(params, &method, body.clone())).await
+        let mut cloned = Request::new(req.body().to_http_body());
+        *cloned.method_mut() = req.method().clone();
+        *cloned.uri_mut() = req.uri().clone();
+        *cloned.version_mut() = req.version();
+        *cloned.headers_mut() = req.headers().clone();
+        if let Some(authorization) = self.proxy.authorization(req.uri())
+        {
+            cloned.headers_mut().insert(PROXY_AUTHORIZATION, authorization);
+        }
+        cloned
     }
 
-    async fn get_body_tls(req: Request<BoxBody>) -> Result<(StatusCode, Bytes), Error>
+    async fn connection(&self) -> Result<&HttpsClient, Error>
     {
-        //let host = req.uri().authority().unwrap().as_str().replace("localhost", "127.0.0.1");
-        let tls = rustls::ClientConfig::builder()
-        .with_native_roots()?
-        .with_no_client_auth();
-        let https = hyper_rustls::HttpsConnectorBuilder::new()
-        .with_tls_config(tls)
-        .https_or_http()
-        .enable_http1()
-        .build();
-        let client: Client<_, Empty<Bytes>> = Client::builder(TokioExecutor::new()).build(https);
-        let fut = async move 
+        self.client.get_or_try_init(|| async
         {
-            let mut req = req;
+            let tls = self.tls.client_config()?;
+            let mut http = HttpConnector::new_with_resolver(proxy::DnsResolver::new());
+            http.enforce_http(false);
+            http.set_connect_timeout(self.connect_timeout);
+            let http = proxy::ProxyConnector::new(http, self.proxy.clone());
+            let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_tls_config(tls)
+            .https_or_http();
+            //протокол по TLS выбирается через ALPN, запросы по соединению HTTP/2 выполняются параллельно
+            let https = match self.http_version
+            {
+                HttpVersionPolicy::Http1Only => https.enable_http1().wrap_connector(http),
+                HttpVersionPolicy::Negotiate => https.enable_all_versions().wrap_connector(http),
+                HttpVersionPolicy::Http2PriorKnowledge => https.enable_http2().wrap_connector(http)
+            };
+            let client: HttpsClient = Client::builder(TokioExecutor::new())
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .http2_only(self.http_version == HttpVersionPolicy::Http2PriorKnowledge)
+            .build(https);
+            Ok::<_, Error>(client)
+        }).await
+    }
+
+    ///ожидание `f` не дольше окончания таймаута попытки
+    async fn until<T>(&self, deadline: tokio::time::Instant, f: impl std::future::Future<Output = Result<T, Error>>) -> Result<T, Error>
+    {
+        tokio::time::timeout_at(deadline, f).await.unwrap_or_else(|_| Err(Error::Timeout(self.uri.to_string())))
+    }
+
+    ///попытка запроса до получения заголовков ответа с таймаутом `timeout`, через цепочку `Middleware`
+    async fn get_body_tls(&self, req: Request<RequestBody>, timeout: Duration) -> Result<RawResponse, Error>
+    {
+        if self.middleware.is_empty()
+        {
+            return self.send_with_redirects(req, timeout).await;
+        }
+        let (mut parts, body) = req.into_parts();
+        self.middleware.before_request(&mut parts)?;
+        let req = Request::from_parts(parts.clone(), body);
+        let span = parts.extensions.get::<tracing::Span>().cloned().unwrap_or_else(tracing::Span::none);
+        match tracing::Instrument::instrument(self.send_with_redirects(req, timeout), span).await
+        {
+            Ok(raw) =>
+            {
+                let (mut response_parts, body) = raw.response.into_parts();
+                if let Err(e) = self.middleware.after_response(&parts, &mut response_parts)
+                {
+                    self.middleware.on_error(&parts, &e);
+                    return Err(e);
+                }
+                Ok(RawResponse { response: Response::from_parts(response_parts, body), ..raw })
+            },
+            Err(e) =>
+            {
+                self.middleware.on_error(&parts, &e);
+                Err(e)
+            }
+        }
+    }
+
+    ///ожидание в ограничениях частоты и одновременных запросов не входит в таймаут попытки `timeout`,
+    ///его ограничивает только общий таймаут
+    async fn send_with_redirects(&self, req: Request<RequestBody>, timeout: Duration) -> Result<RawResponse, Error>
+    {
+        let client = self.connection().await?;
+        let mut req = req;
+        let mut redirects: Vec<Uri> = Vec::new();
+        let mut deadline = tokio::time::Instant::now() + timeout;
+        //заголовок заданный явно имеет приоритет
+        if !req.headers().contains_key(AUTHORIZATION)
+        {
+            if let Some(authorization) = self.until(deadline, self.auth.authorization()).await?
+            {
+                req.headers_mut().insert(AUTHORIZATION, authorization);
+            }
+        }
+        let mut auth_refreshed = false;
+        loop
+        {
+            //ограничения частоты и одновременных запросов проверяются для хоста каждого перехода
+            let (mut permit, waited) = self.acquire(req.uri()).await;
+            deadline += waited;
             tracing::debug!("Отправка запроса на {}, headers: {:?}", req.uri(), req.headers());
-            let mut response = client
-            .get(req.uri().clone())
-            .await?;
-            if let Some(c) = response.headers().get("set-cookie")
-            {
-                let mut guard = COOK.lock().await;
-                //были установлены для данного хоста но обновились
-                if let Some(old) = guard.insert(req.uri().host().unwrap().to_owned(), c.to_str().unwrap().to_owned())
+            let mut response = self.until(deadline, async { client.request(self.clone_request(&req)).await.map_err(proxy::connect_error) }).await?;
+            //сервер установил или поменял куки, делаем повторный запрос с новыми куками,
+            //только для GET и HEAD - остальные методы не должны выполняться на сервере дважды
+            let mut cookies_changed = self.cookies.store_response_cookies(req.uri(), response.headers());
+            if cookies_changed && matches!(*req.method(), Method::GET | Method::HEAD)
+            {
+                if let Some(c) = self.cookies.cookie_header(req.uri())
                 {
-                    //если отличаются от предыдущих, обновляем и делаем повторный запрос
-                    if c.to_str().unwrap() != &old
+                    if req.headers().get(COOKIE) != Some(&c)
                     {
-                        req.headers_mut().insert(COOKIE, c.clone());
-                        tracing::debug!("Поменялись куки, деаем поторный запрос на {}, headers: {:?}", req.uri(), req.headers());
+                        req.headers_mut().insert(COOKIE, c);
+                        tracing::debug!("Установлены новые куки, делаем поторный запрос на {}, headers: {:?}", req.uri(), req.headers());
+                        drop(permit);
+                        let waited;
+                        (permit, waited) = self.acquire(req.uri()).await;
+                        deadline += waited;
+                        response = self.until(deadline, async { client.request(self.clone_request(&req)).await.map_err(proxy::connect_error) }).await?;
+                        cookies_changed |= self.cookies.store_response_cookies(req.uri(), response.headers());
                     }
-                  
                 }
-                //установлены впервые
-                else 
-                {
-                    req.headers_mut().insert(COOKIE, c.clone());
-                    tracing::debug!("Установлены новые куки, делаем поторный запрос на {}, headers: {:?}", req.uri(), req.headers());
-                    
-                }
-                response = client
-                    .get(req.uri().clone())
-                    .await?;
             }
-            //перенаправление запроса при статусе 301
-            if let Some(c) = response.headers().get("location")
+            if response.status() == StatusCode::UNAUTHORIZED && !auth_refreshed
             {
-                tracing::debug!("От сервера получен ответ с перенаправлением запроса на {}", c.to_str().unwrap());
-                if let Ok(new_uri) = c.to_str().unwrap().parse::<Uri>()
+                auth_refreshed = true;
+                if let Some(authorization) = self.until(deadline, self.auth.refresh(req.headers().get(AUTHORIZATION))).await?
                 {
-                    *req.uri_mut() = new_uri;
-                    response = client
-                    .get(req.uri().clone())
-                    .await?;
+                    req.headers_mut().insert(AUTHORIZATION, authorization);
+                    continue;
                 }
             }
             tracing::debug!("От сервера получен ответ со статусом {}, headers: {:?}", response.status(), response.headers());
-            let status = response.status();
-            let body = response
-                .into_body()
-                .collect()
-                .await?
-                .to_bytes();
-            Ok((status, body))
-        };
-    
-        fut.await
+            match self.redirect.next_request(response.status(), response.headers(), &req, &redirects)?
+            {
+                Some(mut next) =>
+                {
+                    //повторный переход на тот же адрес допустим только если сервер поменял куки
+                    if !cookies_changed && (next.uri() == req.uri() || redirects.contains(next.uri()))
+                    {
+                        return Err(Error::RedirectLoop(next.uri().to_string()));
+                    }
+                    match self.cookies.cookie_header(next.uri())
+                    {
+                        Some(c) => next.headers_mut().insert(COOKIE, c),
+                        None => next.headers_mut().remove(COOKIE)
+                    };
+                    redirects.push(req.uri().clone());
+                    req = next;
+                },
+                None => return Ok(RawResponse { response, url: req.uri().clone(), redirects, permit, deadline })
+            }
+        }
+    }
+    ///место и токен в ограничениях для хоста `uri` и время их ожидания
+    async fn acquire(&self, uri: &Uri) -> (Option<tokio::sync::OwnedSemaphorePermit>, Duration)
+    {
+        let started = tokio::time::Instant::now();
+        let permit = self.limiter.acquire(uri).await;
+        (permit, started.elapsed())
     }
 }
 
-static COOK: std::sync::LazyLock<Arc<Mutex<HashMap<String, String>>>> = std::sync::LazyLock::new(|| Arc::new(Mutex::new(HashMap::new())));
-
-
 #[cfg(test)]
 mod tests
 {
-    use hyper::{body::Bytes, header::{HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, CONTENT_TYPE, HOST, ORIGIN, REFERER, UPGRADE_INSECURE_REQUESTS, USER_AGENT}, HeaderMap, Request, Uri};
+    use hyper::{body::Bytes, header::{HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, CONTENT_TYPE, HOST, REFERER, UPGRADE_INSECURE_REQUESTS, USER_AGENT}, HeaderMap, Request, Uri};
+
+    use super::{testing::{Mock, MockResponse, MockServer}, to_body, BoxBody, Method, StatusCode};
+    use std::{collections::HashMap, net::SocketAddr, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+    use http_body_util::{BodyExt, Full};
+    use hyper_util::rt::TokioIo;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Echo
+    {
+        method: String,
+        uri: String,
+        headers: HashMap<String, String>,
+        body: String
+    }
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct Payload
+    {
+        id: u32,
+        name: String
+    }
+
+    ///локальный сервер, возвращает в ответе метод, uri, заголовки и тело полученного запроса
+    async fn echo_server() -> SocketAddr
+    {
+        echo_server_with_counter().await.0
+    }
+    ///то же что `echo_server`, дополнительно считает количество принятых tcp соединений
+    async fn echo_server_with_counter() -> (SocketAddr, Arc<AtomicUsize>)
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        tokio::spawn(async move
+        {
+            loop
+            {
+                let (stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move
+                {
+                    let service = hyper::service::service_fn(|req: Request<hyper::body::Incoming>| async move
+                    {
+                        let redirect = match req.uri().path()
+                        {
+                            "/redirect/303" => Some((hyper::StatusCode::SEE_OTHER, "/api/echo")),
+                            "/redirect/307" => Some((hyper::StatusCode::TEMPORARY_REDIRECT, "/api/echo")),
+                            "/redirect/chain" => Some((hyper::StatusCode::FOUND, "303")),
+                            "/redirect/loop" => Some((hyper::StatusCode::FOUND, "loop")),
+                            _ => None
+                        };
+                        if req.uri().path() == "/slow"
+                        {
+                            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                        }
+                        match req.uri().path()
+                        {
+                            "/file" => return Ok(hyper::Response::new(to_body(test_file()))),
+                            "/status/404" => return Ok(super::error_response("не найдено".to_owned(), hyper::StatusCode::NOT_FOUND)),
+                            #[cfg(feature="decompression")]
+                            p if p.starts_with("/compressed/") =>
+                            {
+                                let encoding = &p["/compressed/".len()..];
+                                let response = hyper::Response::builder()
+                                    .header(super::CONTENT_ENCODING, encoding)
+                                    .body(to_body(compress(encoding, &test_file()).await))
+                                    .unwrap();
+                                return Ok(response);
+                            },
+                            _ => ()
+                        }
+                        if let Some((status, location)) = redirect
+                        {
+                            let response = hyper::Response::builder()
+                                .status(status)
+                                .header(super::LOCATION, location)
+                                .body(to_body(Bytes::new()))
+                                .unwrap();
+                            return Ok(response);
+                        }
+                        let method = req.method().to_string();
+                        let uri = req.uri().to_string();
+                        let login = req.uri().path() == "/login";
+                        let headers = req.headers()
+                            .iter()
+                            .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or_default().to_owned()))
+                            .collect();
+                        let body = String::from_utf8(req.into_body().collect().await?.to_bytes().to_vec()).unwrap();
+                        let mut response = super::json_response(&Echo { method, uri, headers, body });
+                        if login
+                        {
+                            response.headers_mut().append(super::SET_COOKIE, HeaderValue::from_static("session=abc; Path=/; HttpOnly"));
+                            response.headers_mut().append(super::SET_COOKIE, HeaderValue::from_static("lang=ru; Path=/api; Max-Age=3600"));
+                        }
+                        Ok::<_, hyper::Error>(response)
+                    });
+                    //HTTP/1.1 или HTTP/2 без TLS (h2c prior knowledge)
+                    let _ = hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new()).serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        (addr, connections)
+    }
+
+    ///сервер возвращающий ошибки: `/503` всегда 503 с `Retry-After: 0`, `/500` всегда 500,
+    ///`/flaky` первые два запроса 503, затем 200, возвращает количество полученных запросов
+    async fn failing_server() -> (SocketAddr, Arc<AtomicUsize>)
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move
+        {
+            loop
+            {
+                let (stream, _) = listener.accept().await.unwrap();
+                let counter = counter.clone();
+                tokio::spawn(async move
+                {
+                    let service = hyper::service::service_fn(move |req: Request<hyper::body::Incoming>|
+                    {
+                        let hit = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                        async move
+                        {
+                            let response = match req.uri().path()
+                            {
+                                "/503" => hyper::Response::builder()
+                                    .status(hyper::StatusCode::SERVICE_UNAVAILABLE)
+                                    .header(super::RETRY_AFTER, "0")
+                                    .body(to_body(Bytes::new()))
+                                    .unwrap(),
+                                "/flaky" if hit <= 2 => super::empty_response(hyper::StatusCode::SERVICE_UNAVAILABLE),
+                                "/flaky" => super::ok_response("ok".to_owned()),
+                                _ => super::empty_response(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+                            };
+                            Ok::<_, hyper::Error>(response)
+                        }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        (addr, hits)
+    }
+
+    ///содержимое отдаваемое по пути `/file`
+    fn test_file() -> Bytes
+    {
+        Bytes::from((0..300_000u32).map(|i| (i % 251) as u8).collect::<Vec<u8>>())
+    }
+
+    ///сжатие `data` алгоритмом из Content-Encoding
+    #[cfg(feature="decompression")]
+    async fn compress(encoding: &str, data: &[u8]) -> Bytes
+    {
+        use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder, ZlibEncoder};
+        use tokio::io::AsyncReadExt;
+        let mut out = Vec::new();
+        match encoding
+        {
+            "gzip" => GzipEncoder::new(data).read_to_end(&mut out).await,
+            "deflate" => ZlibEncoder::new(data).read_to_end(&mut out).await,
+            "br" => BrotliEncoder::new(data).read_to_end(&mut out).await,
+            _ => { out.extend_from_slice(data); Ok(data.len()) }
+        }.unwrap();
+        Bytes::from(out)
+    }
+
+    ///сервер отдающий `test_file()`, первый запрос на каждый путь обрывается на середине
+    ///`/ranged` поддерживает Range и If-Range, `/plain` диапазоны не поддерживает
+    ///возвращает список полученных запросов (путь, заголовок Range)
+    async fn ranged_server() -> (SocketAddr, Arc<std::sync::Mutex<Vec<(String, Option<String>)>>>)
+    {
+        type ServerBody = http_body_util::combinators::BoxBody<Bytes, std::io::Error>;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = requests.clone();
+        tokio::spawn(async move
+        {
+            loop
+            {
+                let (stream, _) = listener.accept().await.unwrap();
+                let log = log.clone();
+                tokio::spawn(async move
+                {
+                    let service = hyper::service::service_fn(move |req: Request<hyper::body::Incoming>|
+                    {
+                        let log = log.clone();
+                        async move
+                        {
+                            let file = test_file();
+                            let path = req.uri().path().to_owned();
+                            let range = req.headers().get(super::RANGE).map(|r| r.to_str().unwrap().to_owned());
+                            let if_range = req.headers().get(super::IF_RANGE).map(|r| r.to_str().unwrap().to_owned());
+                            let first = {
+                                let mut log = log.lock().unwrap();
+                                let first = !log.iter().any(|(p, _)| p == &path);
+                                log.push((path.clone(), range.clone()));
+                                first
+                            };
+                            let ranged = path == "/ranged";
+                            let mut response = hyper::Response::builder();
+                            if ranged
+                            {
+                                response = response.header(super::ACCEPT_RANGES, "bytes").header(super::ETAG, "\"v1\"");
+                            }
+                            let start = match (ranged, range, if_range.as_deref())
+                            {
+                                (true, Some(r), Some("\"v1\"")) => r.trim_start_matches("bytes=").trim_end_matches('-').parse::<usize>().unwrap(),
+                                _ => 0
+                            };
+                            if start > 0
+                            {
+                                let body: ServerBody = Full::new(file.slice(start..)).map_err(|never| match never {}).boxed();
+                                return Ok::<_, hyper::Error>(response
+                                    .status(hyper::StatusCode::PARTIAL_CONTENT)
+                                    .header(super::CONTENT_LENGTH, file.len() - start)
+                                    .header(super::CONTENT_RANGE, format!("bytes {}-{}/{}", start, file.len() - 1, file.len()))
+                                    .body(body)
+                                    .unwrap());
+                            }
+                            let body: ServerBody = if first
+                            {
+                                use futures::StreamExt;
+                                //пауза перед обрывом чтобы первая часть успела дойти до клиента
+                                let frames = futures::stream::iter(vec![Ok(hyper::body::Frame::data(file.slice(..100_000)))])
+                                    .chain(futures::stream::once(async
+                                    {
+                                        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                                        Err(std::io::Error::other("обрыв соединения"))
+                                    }));
+                                BodyExt::boxed(http_body_util::StreamBody::new(frames))
+                            }
+                            else
+                            {
+                                Full::new(file.clone()).map_err(|never| match never {}).boxed()
+                            };
+                            Ok(response.header(super::CONTENT_LENGTH, file.len()).body(body).unwrap())
+                        }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        (addr, requests)
+    }
+
+    fn echo_client(addr: SocketAddr) -> super::HyperClient
+    {
+        path_client(addr, "/api/echo")
+    }
+    fn path_client(addr: SocketAddr, path: &str) -> super::HyperClient
+    {
+        super::HyperClient::builder(format!("http://{}{}", addr, path))
+        .with_read_timeout(std::time::Duration::from_secs(2))
+        .with_retry_count(1)
+        .with_header("x-test", "echo")
+        .build()
+        .unwrap()
+    }
+
+    fn parse_echo(result: Result<super::ClientResponse, crate::error::Error>) -> Echo
+    {
+        let response = result.unwrap().error_for_status().unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        assert_eq!(response.headers().get(super::CONTENT_TYPE).unwrap(), "application/json");
+        response.json().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_methods_with_body()
+    {
+        let addr = echo_server().await;
+        let client = echo_client(addr);
+        let payload = Payload { id: 1, name: "тест".to_owned() };
+        let expected_body = serde_json::to_string(&payload).unwrap();
+        let results = vec![
+            ("GET", client.get_with_body(payload.clone()).await),
+            ("POST", client.post_with_body(payload.clone()).await),
+            ("PUT", client.put_with_body(payload.clone()).await),
+            ("PATCH", client.patch_with_body(payload.clone()).await),
+            ("DELETE", client.delete_with_body::<&str, _>(payload.clone()).await),
+        ];
+        for (method, result) in results
+        {
+            let echo = parse_echo(result);
+            assert_eq!(echo.method, method);
+            assert_eq!(echo.uri, "/api/echo");
+            assert_eq!(echo.headers.get("x-test").map(|v| v.as_str()), Some("echo"));
+            assert_eq!(echo.headers.get("host"), Some(&addr.to_string()));
+            assert_eq!(echo.body, expected_body);
+            assert_eq!(echo.headers.get("content-type").map(|v| v.as_str()), Some("application/json"));
+        }
+    }
 
-    use super::{to_body, BoxBody};
+    #[tokio::test]
+    async fn test_hyper_cli_json()
+    {
+        let addr = echo_server().await;
+        let client = echo_client(addr);
+        let payload = Payload { id: 1, name: "тест".to_owned() };
+        let echo: Echo = client.post_json(&payload).await.unwrap();
+        assert_eq!(echo.method, "POST");
+        assert_eq!(echo.body, serde_json::to_string(&payload).unwrap());
+        assert_eq!(echo.headers.get("content-type").map(|v| v.as_str()), Some("application/json"));
+        assert_eq!(echo.headers.get("accept").map(|v| v.as_str()), Some("application/json"));
+        let echo: Echo = client.get_json_with_params(&[("q", "1")]).await.unwrap();
+        assert_eq!((echo.method.as_str(), echo.uri.as_str(), echo.body.as_str()), ("GET", "/api/echo?q=1", ""));
+        assert!(!echo.headers.contains_key("content-type"));
+        //заголовок клиента не заменяется
+        let echo: Echo = client.clone().with_header(super::ACCEPT, "application/vnd.api+json").put_json(&payload).await.unwrap();
+        assert_eq!(echo.headers.get("accept").map(|v| v.as_str()), Some("application/vnd.api+json"));
+
+        match client.post_json::<_, Payload>(&payload).await
+        {
+            Err(crate::error::Error::JsonError { status, body, .. }) =>
+            {
+                assert_eq!(status, hyper::StatusCode::OK);
+                assert!(body.starts_with("{\"method\":\"POST\""), "{}", body);
+            },
+            other => panic!("ожидалась ошибка разбора JSON, получено {:?}", other)
+        }
+        match path_client(addr, "/status/404").get_json::<Echo>().await
+        {
+            Err(crate::error::Error::StatusError { status, body, .. }) =>
+            {
+                assert_eq!(status, hyper::StatusCode::NOT_FOUND);
+                assert!(body.contains("не найдено"));
+            },
+            other => panic!("ожидалась ошибка статуса, получено {:?}", other)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_form_and_multipart()
+    {
+        let addr = echo_server().await;
+        let echo = parse_echo(echo_client(addr).post_form(&[("q", "два слова"), ("a&b", "1=2")]).await);
+        assert_eq!(echo.method, "POST");
+        assert_eq!(echo.body, "q=%D0%B4%D0%B2%D0%B0%20%D1%81%D0%BB%D0%BE%D0%B2%D0%B0&a%26b=1%3D2");
+        assert_eq!(echo.headers.get("content-type").map(|v| v.as_str()), Some("application/x-www-form-urlencoded"));
+
+        let dir = std::env::temp_dir().join(format!("utilites_multipart_{}", addr.port()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("document.txt");
+        //файл больше одной части чтения
+        let content = "строка документа\n".repeat(5000);
+        std::fs::write(&path, &content).unwrap();
+        let form = super::Multipart::new()
+            .text("title", "Отчет")
+            .file("document", &path)
+            .file_as("scan", &path, "скан \"1\".txt", "text/plain");
+        let boundary = form.boundary().to_owned();
+        let expected = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nОтчет\r\n\
+            --{b}\r\nContent-Disposition: form-data; name=\"document\"; filename=\"document.txt\"\r\nContent-Type: application/octet-stream\r\n\r\n{c}\r\n\
+            --{b}\r\nContent-Disposition: form-data; name=\"scan\"; filename=\"скан %221%22.txt\"\r\nContent-Type: text/plain\r\n\r\n{c}\r\n\
+            --{b}--\r\n", b = boundary, c = content);
+        //при перенаправлении 307 файлы отправляются повторно
+        for client in [echo_client(addr), path_client(addr, "/redirect/307")]
+        {
+            let echo = parse_echo(client.post_multipart(form.clone()).await);
+            assert_eq!(echo.method, "POST");
+            assert_eq!(echo.headers.get("content-type"), Some(&format!("multipart/form-data; boundary={}", boundary)));
+            assert_eq!(echo.headers.get("content-length"), Some(&expected.len().to_string()));
+            assert!(echo.body == expected);
+        }
+        //Content-Type формы и multipart заменяет заданный в заголовках клиента, у JSON остается клиентский
+        let client = echo_client(addr).with_header(CONTENT_TYPE, "application/json");
+        let echo = parse_echo(client.post_form(&[("q", "1")]).await);
+        assert_eq!(echo.headers.get("content-type").map(|v| v.as_str()), Some("application/x-www-form-urlencoded"));
+        let echo = parse_echo(client.post_multipart(form.clone()).await);
+        assert_eq!(echo.headers.get("content-type"), Some(&format!("multipart/form-data; boundary={}", boundary)));
+        assert!(echo.body == expected);
+        let echo = parse_echo(client.with_header(CONTENT_TYPE, "application/vnd.api+json").request(Method::POST, &[] as &[(&str, &str)], Some(1)).await);
+        assert_eq!(echo.headers.get("content-type").map(|v| v.as_str()), Some("application/vnd.api+json"));
+        let missing = super::Multipart::new().file("document", dir.join("missing.txt"));
+        assert!(matches!(echo_client(addr).post_multipart(missing).await, Err(crate::error::Error::FileOpenError(..))));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_methods_with_params()
+    {
+        let addr = echo_server().await;
+        let client = echo_client(addr);
+        let params = [("q", "два слова"), ("page", "2")];
+        let results = vec![
+            ("GET", client.get_with_params(&params).await),
+            ("POST", client.post_with_params(&params).await),
+            ("PUT", client.put_with_params(&params).await),
+            ("PATCH", client.patch_with_params(&params).await),
+            ("DELETE", client.delete(&params).await),
+        ];
+        for (method, result) in results
+        {
+            let echo = parse_echo(result);
+            assert_eq!(echo.method, method);
+            assert_eq!(echo.uri, "/api/echo?q=%D0%B4%D0%B2%D0%B0%20%D1%81%D0%BB%D0%BE%D0%B2%D0%B0&page=2");
+            assert_eq!(echo.headers.get("x-test").map(|v| v.as_str()), Some("echo"));
+            assert!(echo.body.is_empty());
+        }
+        let echo = parse_echo(client.get().await);
+        assert_eq!(echo.method, "GET");
+        assert_eq!(echo.uri, "/api/echo");
+        assert_eq!(echo.headers.get("x-test").map(|v| v.as_str()), Some("echo"));
+    }
 
     // #[tokio::test]
     // async fn test_cli()
@@ -463,107 +1590,521 @@ mod tests
     async fn test_hyper_cli()
     {
         let _ = logger::StructLogger::new_default();
-        let uri: Uri = "http://95.173.157.133:8000/api/ebpi/redactions?t=%7B%22hash%22%3A%2266c4df9cc6da662d2ee557c6f2e21cf2f84c3ba3d8bcdfeb43d1925ef3149b24%22%2C%22ttl%22%3A3%7D".parse().unwrap();
-      
+        let server = MockServer::start().await;
+        server.mock(Mock::new(Method::GET, "/api/ebpi/redactions")
+            .with_query("t", r#"{"hash":"66c4df9cc6da662d2ee557c6f2e21cf2f84c3ba3d8bcdfeb43d1925ef3149b24","ttl":3}"#)
+            .with_header(HOST, "pravo.gov.ru")
+            .respond(MockResponse::ok().with_body("[]"))
+            .expect(10));
+        let uri = server.url("/api/ebpi/redactions?t=%7B%22hash%22%3A%2266c4df9cc6da662d2ee557c6f2e21cf2f84c3ba3d8bcdfeb43d1925ef3149b24%22%2C%22ttl%22%3A3%7D");
         for i in 0..10
         {
-            //let r = empty_get_request(uri.clone());
             let result = super::get_body_retry(uri.clone()).await;
-            if result.is_err()
-            {
-                tracing::error!("{}->{:?}", i, result);
-            }
-            else {
-                tracing::info!("{:?}", result.as_ref().unwrap());
-            }
+            assert_eq!(result.as_ref().map_err(|e| e.to_string()), Ok(&Bytes::from_static(b"[]")), "попытка {}", i);
         }
-        
+        server.verify();
     }
      #[tokio::test]
     async fn test_hyper_cli2()
     {
         let _ = logger::StructLogger::new_default();
         let attrs = r#"[{"AttrId":5,"AttrMode":0,"DateFrom":"20240101","DateTo":"20240620"},{"AttrId":999,"AttrMode":1,"Words":[50,"-date","20220701",0,1]}]"#;
-        let uri: Uri = format!("http://actual.pravo.gov.ru:8000/api/ebpi/attrsearch?bpa=ebpi&q={}", attrs).parse().unwrap();
-      
+        let server = MockServer::start().await;
+        server.mock(Mock::new(Method::GET, "/api/ebpi/attrsearch")
+            .with_query("bpa", "ebpi")
+            .with_query("q", attrs)
+            .respond(MockResponse::new(StatusCode::SERVICE_UNAVAILABLE))
+            .respond(MockResponse::ok().with_body("{}"))
+            .expect(11));
+        let uri = server.url(&format!("/api/ebpi/attrsearch?bpa=ebpi&q={}", super::encoding::encode(attrs)));
         for i in 0..10
         {
-            //let r = empty_get_request(uri.clone());
+            //первый ответ 503 повторяется
             let result = super::get_body_retry(uri.clone()).await;
-            if result.is_err()
-            {
-                tracing::error!("{}->{:?}", i, result);
-            }
-            else {
-                tracing::info!("{:?}", result.as_ref().unwrap());
-            }
+            assert_eq!(result.as_ref().map_err(|e| e.to_string()), Ok(&Bytes::from_static(b"{}")), "попытка {}", i);
         }
-        
+        server.verify();
     }
 
     #[tokio::test]
     async fn test_hyper_cli_new()
     {
         let _ = logger::StructLogger::new_default();
-        let uri: Uri = "http://pravo.gov.ru:8000/api/ebpi/attrsearch/".parse().unwrap();
-        let hyper_client = super::HyperClient::new(uri)
-        .with_headers(headers());
-        for i in 0..10
+        let q = "[{%22AttrId%22:5,%22AttrMode%22:0,%22DateFrom%22:%2220240101%22,%22DateTo%22:%2220240620%22},{%22AttrId%22:999,%22AttrMode%22:1,%22Words%22:[50,%22-date%22,%2220220701%22,0,1]}]";
+        let server = MockServer::start().await;
+        //завершающий `/` пути отбрасывается при добавлении параметров
+        server.mock(Mock::new(Method::GET, "/api/ebpi/attrsearch")
+            .with_query("bpa", "ebpi")
+            .with_query("q", q)
+            .with_header(USER_AGENT, "Mozilla/5.0 (X11; Linux x86_64; rv:127.0) Gecko/20100101 Firefox/127.0")
+            .with_header(REFERER, "http:://pravo.gov.ru")
+            .respond(MockResponse::ok().with_json(&[Payload { id: 1, name: "документ".to_owned() }]))
+            .expect(10));
+        let hyper_client = super::HyperClient::builder(server.url("/api/ebpi/attrsearch/"))
+        .with_headers(headers())
+        .build()
+        .unwrap();
+        for _ in 0..10
         {
-            //let r = empty_get_request(uri.clone());
-            let result = hyper_client.get_with_params(&[("bpa", "ebpi"), ("q", "[{%22AttrId%22:5,%22AttrMode%22:0,%22DateFrom%22:%2220240101%22,%22DateTo%22:%2220240620%22},{%22AttrId%22:999,%22AttrMode%22:1,%22Words%22:[50,%22-date%22,%2220220701%22,0,1]}]")]).await;
-            if result.is_err()
-            {
-                tracing::error!("{}->{:?}", i, result);
-            }
-            else {
-                tracing::info!("{:?}", result.as_ref().unwrap());
-            }
+            let result = hyper_client.get_with_params(&[("bpa", "ebpi"), ("q", q)]).await.unwrap();
+            assert_eq!(result.json::<Vec<Payload>>().unwrap(), vec![Payload { id: 1, name: "документ".to_owned() }]);
         }
+        server.verify();
     }
 
     #[tokio::test]
     async fn test_hyper_cli_tls()
     {
-        let _ = logger::StructLogger::new_default();
-        let uri: Uri = "https://fake-json-api.mock.beeceptor.com/companies".parse().unwrap();
-        let hyper_client = super::HyperClient::new(uri)
-        .with_headers(headers2());
+        let addr = super::tls::tests::tls_server(false).await;
+        let hyper_client = super::HyperClient::builder(format!("https://127.0.0.1:{}/companies", addr.port()))
+        .with_headers(headers2())
+        .with_root_certificates_pem(super::tls::tests::CA)
+        .with_retry_count(1)
+        .build()
+        .unwrap();
+        //запросы по одному соединению TLS
         for i in 0..10
         {
-            //let r = empty_get_request(uri.clone());
-            let result = hyper_client.get().await;
-            if result.is_err()
-            {
-                tracing::error!("{}->{:?}", i, result);
-            }
-            else {
-                tracing::info!("{:?}", result.as_ref().unwrap());
-            }
+            let response = hyper_client.get().await.unwrap_or_else(|e| panic!("{}->{:?}", i, e));
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.text(), "ok");
         }
     }
     #[tokio::test]
     async fn test_hyper_cli_tls_retirn301()
     {
-        let _ = logger::StructLogger::new_default();
-                      //https://egov-buryatia.ru/npa_template?date_doc_from=2024-01-01&date_doc_to=2024-12-31&TIP_DOC=%D0%97%D0%B0%D0%BA%D0%BE%D0%BD&ORGAN_VLASTI=%D0%9D%D0%B0%D1%80%D0%BE%D0%B4%D0%BD%D1%8B%D0%B9+%D0%A5%D1%83%D1%80%D0%B0%D0%BB&PAGEN_1=1
-        let uri: Uri = "https://egov-buryatia.ru/npa_template?date_doc_from=2024-01-01&date_doc_to=2024-12-31&TIP_DOC=%D0%97%D0%B0%D0%BA%D0%BE%D0%BD&ORGAN_VLASTI=%D0%9D%D0%B0%D1%80%D0%BE%D0%B4%D0%BD%D1%8B%D0%B9+%D0%A5%D1%83%D1%80%D0%B0%D0%BB&PAGEN_1=1".parse().unwrap();
-        let client = super::HyperClient::new(uri).with_headers(vec![
-            (ACCEPT_ENCODING, "gzip, deflate, br, zstd"),
+        let addr = super::tls::tests::tls_server(false).await;
+        let target = format!("https://127.0.0.1:{}/npa_template?PAGEN_1=1", addr.port());
+        let server = MockServer::start().await;
+        server.mock(Mock::new(Method::GET, "/npa_template")
+            .with_query("PAGEN_1", "1")
+            .respond(MockResponse::new(StatusCode::MOVED_PERMANENTLY).with_header(super::LOCATION, &target))
+            .expect(1));
+        let client = super::HyperClient::builder(server.url("/npa_template?PAGEN_1=1")).with_headers(vec![
             (ACCEPT, "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"),
-            (HOST, "egov-buryatia.ru"),
             (USER_AGENT, "Mozilla/5.0 (X11; Linux x86_64; rv:134.0) Gecko/20100101 Firefox/134.0"),
             (HeaderName::from_static("sec-fetch-dest"), "document"),
             (HeaderName::from_static("sec-fetch-mode"), "navigate"),
             (UPGRADE_INSECURE_REQUESTS, "1"),
             (ACCEPT_LANGUAGE, "ru-RU,ru;q=0.8,en-US;q=0.5,en;q=0.3"),
-            (CONTENT_TYPE, "application/octet-stream"),
-            (ORIGIN, "https://egov-buryatia.ru"),
             (HeaderName::from_static("x-requested-with"), "XMLHttpRequest")
-        ]);
-        let res = client.get().await;
-        tracing::info!("{:?}", res);
+        ])
+        .with_root_certificates_pem(super::tls::tests::CA)
+        .with_retry_count(1)
+        .build()
+        .unwrap();
+        let response = client.get().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text(), "ok");
+        assert_eq!(response.url().to_string(), target);
+        assert_eq!(response.redirects(), &[server.url("/npa_template?PAGEN_1=1")]);
+        server.verify();
+    }
+    #[tokio::test]
+    async fn test_hyper_cli_reuses_connections()
+    {
+        let (addr, connections) = echo_server_with_counter().await;
+        let client = echo_client(addr);
+        let cloned = client.clone();
+        for _ in 0..5
+        {
+            parse_echo(client.get().await);
+            parse_echo(cloned.post_with_body(1).await);
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_pool_disabled()
+    {
+        let (addr, connections) = echo_server_with_counter().await;
+        let client = echo_client(addr).with_pool_max_idle_per_host(0);
+        for _ in 0..3
+        {
+            parse_echo(client.get().await);
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_cookie_jar()
+    {
+        let addr = echo_server().await;
+        let login = path_client(addr, "/login");
+        //после установки куки запрос повторяется уже с ними
+        let echo = parse_echo(login.get().await);
+        assert_eq!(echo.headers.get("cookie").map(|v| v.as_str()), Some("session=abc"));
+        assert_eq!(login.get_cookie_jar().cookies().len(), 2);
+
+        let client = echo_client(addr).with_cookie_jar(login.get_cookie_jar().clone());
+        let echo = parse_echo(client.get().await);
+        assert_eq!(echo.headers.get("cookie").map(|v| v.as_str()), Some("lang=ru; session=abc"));
+        //у другого клиента свое хранилище
+        let echo = parse_echo(echo_client(addr).get().await);
+        assert!(!echo.headers.contains_key("cookie"));
+
+        //POST с установкой куки не повторяется, куки используются в следующих запросах
+        let server = MockServer::start().await;
+        server
+            .mock(Mock::new(Method::POST, "/login").respond(MockResponse::ok().with_header(super::SET_COOKIE, "session=xyz")).expect(1))
+            .mock(Mock::new(Method::GET, "/profile").with_header(super::COOKIE, "session=xyz").expect(1));
+        let login = super::HyperClient::builder(server.url("/login")).with_retry_count(1).build().unwrap();
+        let payload = Payload { id: 1, name: "login".to_owned() };
+        assert_eq!(login.post_with_body(&payload).await.unwrap().status(), StatusCode::OK);
+        let profile = super::HyperClient::builder(server.url("/profile"))
+            .with_retry_count(1)
+            .build()
+            .unwrap()
+            .with_cookie_jar(login.get_cookie_jar().clone())
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(profile.status(), StatusCode::OK);
+        server.verify();
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_redirects()
+    {
+        let addr = echo_server().await;
+        let payload = Payload { id: 1, name: "redirect".to_owned() };
+        let url = |path: &str| format!("http://{}{}", addr, path).parse::<Uri>().unwrap();
+        //303 меняет метод на GET и отбрасывает тело
+        let response = path_client(addr, "/redirect/303").request(super::Method::POST, &[("q", "1")], Some(payload.clone())).await.unwrap();
+        assert_eq!(response.url(), &url("/api/echo"));
+        assert_eq!(response.redirects(), &[url("/redirect/303?q=1")]);
+        let echo: Echo = response.json().unwrap();
+        assert_eq!(echo.method, "GET");
+        assert_eq!(echo.headers.get("x-test").map(|v| v.as_str()), Some("echo"));
+        assert!(echo.body.is_empty());
+        //307 сохраняет метод и тело
+        let echo = parse_echo(path_client(addr, "/redirect/307").post_with_body(payload.clone()).await);
+        assert_eq!(echo.method, "POST");
+        assert_eq!(echo.body, serde_json::to_string(&payload).unwrap());
+        //относительный Location и несколько перенаправлений
+        let response = path_client(addr, "/redirect/chain").request(super::Method::GET, &[] as &[(&str, &str)], None::<bool>).await.unwrap();
+        assert_eq!(response.url(), &url("/api/echo"));
+        assert_eq!(response.redirects(), &[url("/redirect/chain"), url("/redirect/303")]);
+        //ограничения
+        let chain = path_client(addr, "/redirect/chain");
+        let result = chain.clone().with_redirect_policy(super::RedirectPolicy::Limited(1)).get().await;
+        assert!(matches!(result, Err(crate::error::Error::TooManyRedirects(_))));
+        let response = chain.with_redirect_policy(super::RedirectPolicy::None).get().await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::FOUND);
+        assert_eq!(response.headers().get(super::LOCATION).unwrap(), "303");
+        let result = path_client(addr, "/redirect/loop").get().await;
+        assert!(matches!(result, Err(crate::error::Error::RedirectLoop(_))));
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_stream()
+    {
+        use futures::StreamExt;
+        let addr = echo_server().await;
+        let response = path_client(addr, "/file").get_stream().await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        assert_eq!(response.content_length(), Some(test_file().len() as u64));
+        let mut stream = response.bytes_stream();
+        let mut received = Vec::new();
+        while let Some(chunk) = stream.next().await
+        {
+            received.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(received, test_file());
     }
+
+    #[tokio::test]
+    async fn test_hyper_cli_download_to()
+    {
+        let addr = echo_server().await;
+        let dir = std::env::temp_dir().join(format!("utilites_download_{}", addr.port()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.bin");
+        let mut calls = Vec::new();
+        let size = path_client(addr, "/file").download_to(&path, |done, total| calls.push((done, total))).await.unwrap();
+        assert_eq!(size, test_file().len() as u64);
+        assert_eq!(std::fs::read(&path).unwrap(), test_file());
+        assert!(!crate::io::part_file_path(&path).exists());
+        assert_eq!(calls.last(), Some(&(size, Some(size))));
+        assert!(calls.windows(2).all(|w| w[0].0 < w[1].0));
+
+        let missing = dir.join("missing.bin");
+        let result = path_client(addr, "/status/404").download_to(&missing, |_, _| ()).await;
+        assert!(matches!(result, Err(crate::error::Error::StatusError { status: hyper::StatusCode::NOT_FOUND, .. })));
+        assert!(!missing.exists());
+        assert!(!crate::io::part_file_path(&missing).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature="decompression")]
+    #[tokio::test]
+    async fn test_hyper_cli_decompression()
+    {
+        use futures::StreamExt;
+        let addr = echo_server().await;
+        for encoding in ["gzip", "deflate", "br"]
+        {
+            let path = format!("/compressed/{}", encoding);
+            let response = path_client(addr, &path).get().await.unwrap();
+            assert_eq!(response.bytes(), &test_file(), "{}", encoding);
+            assert!(response.headers().get(super::CONTENT_ENCODING).is_none());
+            assert!(response.headers().get(super::CONTENT_LENGTH).is_none());
+
+            let mut received = Vec::new();
+            let mut stream = path_client(addr, &path).get_stream().await.unwrap().bytes_stream();
+            while let Some(chunk) = stream.next().await
+            {
+                received.extend_from_slice(&chunk.unwrap());
+            }
+            assert_eq!(received, test_file(), "{}", encoding);
+
+            let raw = path_client(addr, &path).with_decompression(false).get().await.unwrap();
+            assert_eq!(raw.bytes(), &compress(encoding, &test_file()).await);
+            assert_eq!(raw.headers().get(super::CONTENT_ENCODING).unwrap(), encoding);
+        }
+        let echo = parse_echo(echo_client(addr).get().await);
+        assert_eq!(echo.headers.get("accept-encoding").map(|e| e.as_str()), Some("gzip, deflate, br"));
+        let echo = parse_echo(echo_client(addr).with_decompression(false).get().await);
+        assert!(!echo.headers.contains_key("accept-encoding"));
+        let echo = parse_echo(echo_client(addr).with_headers(vec![(ACCEPT_ENCODING, "identity".to_owned())]).get().await);
+        assert_eq!(echo.headers.get("accept-encoding").map(|e| e.as_str()), Some("identity"));
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_retry()
+    {
+        let client = |addr: SocketAddr, path: &str| super::HyperClient::builder(format!("http://{}{}", addr, path))
+            .with_read_timeout(std::time::Duration::from_secs(1))
+            .with_backoff(std::time::Duration::from_millis(10), std::time::Duration::from_millis(20))
+            .with_retry_count(3)
+            .build()
+            .unwrap();
+
+        //временная ошибка повторяется до успешного ответа
+        let (addr, hits) = failing_server().await;
+        let response = client(addr, "/flaky").get().await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        //после всех попыток возвращается последний ответ
+        let (addr, hits) = failing_server().await;
+        let response = client(addr, "/503").get().await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        //500 не входит в список статусов по умолчанию
+        let (addr, hits) = failing_server().await;
+        let response = client(addr, "/500").get().await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        let policy = super::RetryPolicy::default().with_statuses(vec![hyper::StatusCode::INTERNAL_SERVER_ERROR]);
+        client(addr, "/500").with_retry_policy(policy).get().await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+
+        //POST повторяется только если это разрешено
+        let (addr, hits) = failing_server().await;
+        client(addr, "/503").post_with_body(Payload { id: 1, name: "x".to_owned() }).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        let policy = super::RetryPolicy::default().with_non_idempotent(true);
+        client(addr, "/503").with_retry_policy(policy).post_with_body(Payload { id: 1, name: "x".to_owned() }).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+
+        //ошибка TLS не повторяется
+        let (addr, connections) = echo_server_with_counter().await;
+        let result = super::HyperClient::builder(format!("https://{}/", addr)).with_retry_count(3).build().unwrap().get().await;
+        assert!(matches!(result, Err(crate::error::Error::HttpClientLegacyError(_))), "{:?}", result);
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        //ошибка разрешения имени не повторяется, домен .invalid не существует (RFC 6761)
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let result = super::HyperClient::builder("http://utilites-test.invalid/")
+            .with_retry_count(3)
+            .with_middleware(super::TimingMiddleware::new(move |_, _, _| { counter.fetch_add(1, Ordering::SeqCst); }))
+            .build()
+            .unwrap()
+            .get()
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_rate_limit()
+    {
+        use std::time::{Duration, Instant};
+        //одновременно выполняется один запрос, клоны клиента разделяют ограничение
+        let addr = echo_server().await;
+        let client = super::HyperClient::builder(format!("http://{}/slow", addr))
+            .with_max_in_flight_per_host(1)
+            .with_retry_count(1)
+            .build()
+            .unwrap();
+        let started = Instant::now();
+        let responses = futures::future::join_all((0..3).map(|_| { let client = client.clone(); async move { client.get().await } })).await;
+        assert!(responses.iter().all(|r| r.is_ok()));
+        assert!(started.elapsed() >= Duration::from_millis(1500), "{:?}", started.elapsed());
+
+        //повторы тоже ждут своей очереди
+        let (addr, hits) = failing_server().await;
+        let started = Instant::now();
+        let response = super::HyperClient::builder(format!("http://{}/flaky", addr))
+            .with_rate_limit(5.0, 1)
+            .with_backoff(Duration::ZERO, Duration::ZERO)
+            .with_retry_count(5)
+            .build()
+            .unwrap()
+            .get().await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+        assert!(started.elapsed() >= Duration::from_millis(400), "{:?}", started.elapsed());
+
+        //перенаправление на другой хост занимает место в ограничении этого хоста
+        let (first, second) = (MockServer::start().await, MockServer::start().await);
+        second.mock(Mock::new(Method::GET, "/slow").respond(MockResponse::ok().with_delay(Duration::from_millis(300))));
+        first.mock(Mock::new(Method::GET, "/redirect").respond(MockResponse::new(StatusCode::FOUND).with_header(super::LOCATION, second.url("/slow").to_string())));
+        let client = super::HyperClient::builder(first.uri()).with_max_in_flight_per_host(1).with_retry_count(1).build().unwrap();
+        let started = Instant::now();
+        let responses = client.fetch_all([first.url("/redirect"), second.url("/slow")], 2).await;
+        assert!(responses.iter().all(|r| r.as_ref().is_ok_and(|r| r.status() == StatusCode::OK)));
+        assert!(started.elapsed() >= Duration::from_millis(600), "{:?}", started.elapsed());
+
+        //ожидание в очереди не входит в таймаут попытки
+        let server = MockServer::start().await;
+        server.mock(Mock::new(Method::GET, "/slow").respond(MockResponse::ok().with_delay(Duration::from_millis(200))));
+        server.mock(Mock::new(Method::GET, "/fast").respond(MockResponse::ok()));
+        let client = super::HyperClient::builder(server.uri())
+            .with_max_in_flight_per_host(1)
+            .with_read_timeout(Duration::from_millis(500))
+            .with_retry_count(1)
+            .build()
+            .unwrap();
+        let responses = client.fetch_all(vec![server.url("/slow"); 6], 6).await;
+        assert!(responses.iter().all(|r| r.as_ref().is_ok_and(|r| r.status() == StatusCode::OK)), "{:?}", responses);
+        let client = super::HyperClient::builder(server.uri())
+            .with_rate_limit(5.0, 1)
+            .with_read_timeout(Duration::from_millis(300))
+            .with_retry_count(1)
+            .build()
+            .unwrap();
+        let responses = client.fetch_all(vec![server.url("/fast"); 5], 5).await;
+        assert!(responses.iter().all(|r| r.as_ref().is_ok_and(|r| r.status() == StatusCode::OK)), "{:?}", responses);
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_builder_timeouts()
+    {
+        use std::time::{Duration, Instant};
+        let builder = |addr: SocketAddr, path: &str| super::HyperClient::builder(format!("http://{}{}", addr, path));
+
+        //таймаут попытки: тип ошибки показывает, что сработал именно он
+        let addr = echo_server().await;
+        let result = builder(addr, "/slow").with_read_timeout(Duration::from_millis(100)).with_retry_count(1).build().unwrap().get().await;
+        assert!(matches!(result, Err(crate::error::Error::Timeout(_))), "{:?}", result);
+
+        //задержка между повторами не зависит от таймаута попытки (иначе два повтора заняли бы не меньше минуты)
+        let (addr, hits) = failing_server().await;
+        let started = Instant::now();
+        let response = builder(addr, "/flaky")
+            .with_read_timeout(Duration::from_secs(30))
+            .with_backoff(Duration::from_millis(10), Duration::from_millis(20))
+            .with_retry_count(5)
+            .build()
+            .unwrap()
+            .get().await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+        assert!(started.elapsed() < Duration::from_secs(20), "{:?}", started.elapsed());
+
+        //общий таймаут прекращает бесконечные повторы
+        let (addr, hits) = failing_server().await;
+        let response = tokio::time::timeout(Duration::from_secs(30), builder(addr, "/500")
+            .with_retry_policy(super::RetryPolicy::default().with_statuses(vec![hyper::StatusCode::INTERNAL_SERVER_ERROR]))
+            .with_backoff(Duration::from_millis(50), Duration::from_millis(60))
+            .with_retry_count(0)
+            .with_total_timeout(Duration::from_millis(300))
+            .build()
+            .unwrap()
+            .get()).await.expect("повторы не прекратились").unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(hits.load(Ordering::SeqCst) >= 2);
+
+        //таймаут подключения: локальный сокет с заполненной очередью не принимает новых соединений
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let listener = socket.listen(0).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut backlog = Vec::new();
+        while let Ok(Ok(stream)) = tokio::time::timeout(Duration::from_millis(200), tokio::net::TcpStream::connect(addr)).await
+        {
+            backlog.push(stream);
+        }
+        let result = builder(addr, "/")
+            .with_connect_timeout(Duration::from_millis(100))
+            .with_read_timeout(Duration::from_secs(30))
+            .with_retry_count(1)
+            .build()
+            .unwrap()
+            .get().await;
+        assert!(matches!(result, Err(crate::error::Error::HttpClientLegacyError(_))), "{:?}", result);
+        drop(listener);
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_http2_prior_knowledge()
+    {
+        use std::time::{Duration, Instant};
+        let (addr, connections) = echo_server_with_counter().await;
+        let client = super::HyperClient::builder(format!("http://{}/slow", addr))
+            .with_http_version(super::HttpVersionPolicy::Http2PriorKnowledge)
+            .with_read_timeout(Duration::from_secs(5))
+            .with_retry_count(1)
+            .build()
+            .unwrap();
+        //запросы выполняются параллельно по одному соединению
+        let started = Instant::now();
+        let responses = futures::future::join_all((0..10).map(|_| client.get())).await;
+        assert!(started.elapsed() < Duration::from_millis(4000), "{:?}", started.elapsed());
+        for response in responses
+        {
+            assert_eq!(response.unwrap().version(), hyper::Version::HTTP_2);
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+        //по умолчанию без TLS используется HTTP/1.1
+        let response = echo_client(addr).get().await.unwrap();
+        assert_eq!(response.version(), hyper::Version::HTTP_11);
+    }
+
+    #[tokio::test]
+    async fn test_hyper_cli_download_resumable()
+    {
+        let (addr, requests) = ranged_server().await;
+        let dir = std::env::temp_dir().join(format!("utilites_resume_{}", addr.port()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let client = |path: &str| super::HyperClient::builder(format!("http://{}{}", addr, path))
+            .with_read_timeout(std::time::Duration::from_millis(200))
+            .with_backoff(std::time::Duration::from_millis(100), std::time::Duration::from_millis(200))
+            .with_retry_count(3)
+            .build()
+            .unwrap();
+        let range_log = |path: &str| requests.lock().unwrap().iter().filter(|(p, _)| p == path).map(|(_, r)| r.clone()).collect::<Vec<_>>();
+
+        //после обрыва запрашивается только недостающая часть
+        let path = dir.join("ranged.bin");
+        let size = client("/ranged").download_to_resumable(&path, |_, _| ()).await.unwrap();
+        assert_eq!(size, test_file().len() as u64);
+        assert_eq!(std::fs::read(&path).unwrap(), test_file());
+        assert_eq!(range_log("/ranged"), vec![None, Some("bytes=100000-".to_owned())]);
+        assert!(!crate::io::part_file_path(&path).exists());
+
+        //сервер не поддерживает диапазоны, файл загружается заново
+        let path = dir.join("plain.bin");
+        client("/plain").download_to_resumable(&path, |_, _| ()).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), test_file());
+        assert_eq!(range_log("/plain"), vec![None, None]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     fn headers2() -> Vec<(HeaderName, String)>
     {
         let mut h= Vec::new();
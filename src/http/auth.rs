@@ -0,0 +1,292 @@
+use std::{fmt::Debug, sync::Arc, time::{Duration, Instant}};
+use base64ct::{Base64, Encoding};
+use futures::future::BoxFuture;
+use hyper::header::HeaderValue;
+use serde::Deserialize;
+use super::HyperClient;
+use crate::error::Error;
+
+///Источник токена для заголовка `Authorization: Bearer`, вызывается перед каждой попыткой запроса
+pub trait TokenProvider: Send + Sync
+{
+    ///действующий токен, `rejected` - токен на который сервер ответил 401,
+    ///если он совпадает с текущим, токен нужно получить заново
+    fn token<'a>(&'a self, rejected: Option<&'a str>) -> BoxFuture<'a, Result<String, Error>>;
+}
+
+///Авторизация запросов `HyperClient`
+#[derive(Clone, Default)]
+pub(crate) enum Auth
+{
+    #[default]
+    None,
+    ///готовое значение заголовка Authorization
+    Header(HeaderValue),
+    Provider(Arc<dyn TokenProvider>)
+}
+
+impl Debug for Auth
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        //секреты в лог не попадают
+        match self
+        {
+            Auth::None => f.write_str("None"),
+            Auth::Header(h) => write!(f, "Header({} ***)", h.to_str().ok().and_then(|h| h.split(' ').next()).unwrap_or_default()),
+            Auth::Provider(_) => f.write_str("Provider")
+        }
+    }
+}
+
+impl Auth
+{
+    pub(crate) fn basic(user: &str, password: &str) -> Result<Self, Error>
+    {
+        let credentials = Base64::encode_string(format!("{}:{}", user, password).as_bytes());
+        Ok(Auth::Header(Self::header("Basic", &credentials)?))
+    }
+    pub(crate) fn bearer(token: &str) -> Result<Self, Error>
+    {
+        Ok(Auth::Header(Self::header("Bearer", token)?))
+    }
+    fn header(scheme: &str, credentials: &str) -> Result<HeaderValue, Error>
+    {
+        let mut value = HeaderValue::from_str(&format!("{} {}", scheme, credentials)).map_err(|e| Error::ConfigError(format!("некорректные данные авторизации: {}", e)))?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+    ///значение заголовка Authorization для нового запроса
+    pub(crate) async fn authorization(&self) -> Result<Option<HeaderValue>, Error>
+    {
+        match self
+        {
+            Auth::None => Ok(None),
+            Auth::Header(h) => Ok(Some(h.clone())),
+            Auth::Provider(p) => Ok(Some(Self::header("Bearer", &p.token(None).await?)?))
+        }
+    }
+    ///новое значение заголовка после ответа 401 на запрос с заголовком `current`,
+    ///None если повторять запрос бессмысленно (токен не поменялся или авторизация задана не через `TokenProvider`)
+    pub(crate) async fn refresh(&self, current: Option<&HeaderValue>) -> Result<Option<HeaderValue>, Error>
+    {
+        let provider = match self
+        {
+            Auth::Provider(p) => p,
+            _ => return Ok(None)
+        };
+        //заголовок удален при перенаправлении на другой хост
+        let rejected = match current.and_then(|h| h.to_str().ok()).and_then(|h| h.strip_prefix("Bearer "))
+        {
+            Some(t) => t,
+            None => return Ok(None)
+        };
+        let token = provider.token(Some(rejected)).await?;
+        if token == rejected
+        {
+            return Ok(None);
+        }
+        tracing::debug!("Токен отклонен сервером, запрос повторяется с новым токеном");
+        Ok(Some(Self::header("Bearer", &token)?))
+    }
+}
+
+///Получение токена OAuth2 по client credentials (RFC 6749, 4.4),
+///токен хранится до истечения `expires_in` (за 30 секунд до него запрашивается новый)
+///```ignore
+///let tokens = OAuth2ClientCredentials::new(HyperClient::builder("https://auth.example.com/oauth/token").build()?, "id", "secret")
+///    .with_scope("read write");
+///let client = HyperClient::builder("https://api.example.com").with_token_provider(tokens).build()?;
+///```
+pub struct OAuth2ClientCredentials
+{
+    client: HyperClient,
+    scope: Option<String>,
+    ///токен и время до которого он действует
+    cache: tokio::sync::Mutex<Option<(String, Option<Instant>)>>
+}
+
+#[derive(Deserialize)]
+struct TokenResponse
+{
+    access_token: String,
+    expires_in: Option<u64>
+}
+
+impl OAuth2ClientCredentials
+{
+    ///токен можно использовать пока до его истечения больше этого времени
+    const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+    ///`token_client` - клиент с адресом получения токена, идентификатор и секрет передаются в заголовке Basic
+    pub fn new<I: AsRef<str>, S: AsRef<str>>(token_client: HyperClient, client_id: I, client_secret: S) -> Self
+    {
+        Self
+        {
+            client: token_client.with_basic_auth(client_id, client_secret),
+            scope: None,
+            cache: tokio::sync::Mutex::new(None)
+        }
+    }
+    pub fn with_scope<S: Into<String>>(mut self, scope: S) -> Self
+    {
+        self.scope = Some(scope.into());
+        self
+    }
+    async fn fetch(&self) -> Result<(String, Option<Instant>), Error>
+    {
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = &self.scope
+        {
+            form.push(("scope", scope));
+        }
+        let response: TokenResponse = self.client.post_form(&form).await?.error_for_status()?.json()?;
+        tracing::debug!("Получен токен от {}, действует {:?} секунд", self.client.get_uri(), response.expires_in);
+        let expires = response.expires_in.map(|e| Instant::now() + Duration::from_secs(e));
+        Ok((response.access_token, expires))
+    }
+}
+
+impl TokenProvider for OAuth2ClientCredentials
+{
+    fn token<'a>(&'a self, rejected: Option<&'a str>) -> BoxFuture<'a, Result<String, Error>>
+    {
+        Box::pin(async move
+        {
+            //блокировка на время запроса, чтобы параллельные запросы не получали токен одновременно
+            let mut cache = self.cache.lock().await;
+            if let Some((token, expires)) = cache.as_ref()
+            {
+                let expired = expires.is_some_and(|e| e.saturating_duration_since(Instant::now()) <= Self::EXPIRY_MARGIN);
+                if !expired && rejected != Some(token.as_str())
+                {
+                    return Ok(token.clone());
+                }
+            }
+            let (token, expires) = self.fetch().await?;
+            *cache = Some((token.clone(), expires));
+            Ok(token)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{net::SocketAddr, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+    use http_body_util::BodyExt;
+    use hyper::{body::Bytes, header::{AUTHORIZATION, CONTENT_TYPE}, Request, StatusCode};
+    use hyper_util::rt::TokioIo;
+    use super::OAuth2ClientCredentials;
+    use crate::{error::Error, http::{to_body, HyperClient}};
+
+    ///`/token` выдает токены `t1`, `t2`... (`expires_in` 3600, для `/short_token` 10),
+    ///`/api` отвечает 200 с заголовком Authorization в теле, если токен последний выданный или задан не Bearer, иначе 401
+    async fn auth_server() -> (SocketAddr, Arc<AtomicUsize>)
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let issued = Arc::new(AtomicUsize::new(0));
+        let counter = issued.clone();
+        tokio::spawn(async move
+        {
+            loop
+            {
+                let (stream, _) = listener.accept().await.unwrap();
+                let counter = counter.clone();
+                tokio::spawn(async move
+                {
+                    let service = hyper::service::service_fn(move |req: Request<hyper::body::Incoming>|
+                    {
+                        let counter = counter.clone();
+                        async move
+                        {
+                            let authorization = req.headers().get(AUTHORIZATION).map(|a| a.to_str().unwrap().to_owned()).unwrap_or_default();
+                            let path = req.uri().path().to_owned();
+                            let response = match path.as_str()
+                            {
+                                "/token" | "/short_token" =>
+                                {
+                                    assert_eq!(authorization, "Basic aWQ6c2VjcmV0");
+                                    assert_eq!(req.headers().get(CONTENT_TYPE).unwrap(), "application/x-www-form-urlencoded");
+                                    let form = req.into_body().collect().await?.to_bytes();
+                                    assert_eq!(form, Bytes::from_static(b"grant_type=client_credentials&scope=read"));
+                                    let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                                    let expires = if path == "/token" { 3600 } else { 10 };
+                                    crate::http::json_response(&serde_json::json!({"access_token": format!("t{}", n), "token_type": "bearer", "expires_in": expires}))
+                                },
+                                _ =>
+                                {
+                                    let current = format!("Bearer t{}", counter.load(Ordering::SeqCst));
+                                    if authorization.starts_with("Bearer ") && authorization != current
+                                    {
+                                        crate::http::error_empty_response(StatusCode::UNAUTHORIZED)
+                                    }
+                                    else
+                                    {
+                                        hyper::Response::new(to_body(Bytes::from(authorization)))
+                                    }
+                                }
+                            };
+                            Ok::<_, hyper::Error>(response)
+                        }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        (addr, issued)
+    }
+
+    fn api(addr: SocketAddr) -> crate::http::HyperClientBuilder
+    {
+        HyperClient::builder(format!("http://{}/api", addr)).with_retry_count(1)
+    }
+    fn tokens(addr: SocketAddr, path: &str) -> OAuth2ClientCredentials
+    {
+        let client = HyperClient::builder(format!("http://{}{}", addr, path)).with_retry_count(1).build().unwrap();
+        OAuth2ClientCredentials::new(client, "id", "secret").with_scope("read")
+    }
+
+    #[tokio::test]
+    async fn test_auth_basic_and_bearer()
+    {
+        let (addr, _) = auth_server().await;
+        let response = api(addr).with_basic_auth("user", "secret").build().unwrap().get().await.unwrap();
+        assert_eq!(response.text(), "Basic dXNlcjpzZWNyZXQ=");
+        let response = api(addr).build().unwrap().with_bearer_auth("t0").get().await.unwrap();
+        assert_eq!(response.text(), "Bearer t0");
+        //заголовок заданный явно имеет приоритет
+        let response = api(addr).with_basic_auth("user", "secret").with_header(AUTHORIZATION, "Custom 1").build().unwrap().get().await.unwrap();
+        assert_eq!(response.text(), "Custom 1");
+        //без TokenProvider ответ 401 не повторяется
+        let result = api(addr).with_bearer_auth("t5").build().unwrap().get().await.unwrap();
+        assert_eq!(result.status(), StatusCode::UNAUTHORIZED);
+        assert!(matches!(api(addr).with_bearer_auth("перевод\nстроки").build(), Err(Error::ConfigError(_))));
+        assert!(!format!("{:?}", api(addr).with_basic_auth("user", "secret").build().unwrap()).contains("dXNlcjpzZWNyZXQ"));
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_provider()
+    {
+        let (addr, issued) = auth_server().await;
+        let client = api(addr).with_token_provider(tokens(addr, "/token")).build().unwrap();
+        assert_eq!(client.get().await.unwrap().text(), "Bearer t1");
+        //токен хранится до истечения и общий для клонов клиента
+        assert_eq!(client.clone().get().await.unwrap().text(), "Bearer t1");
+        assert_eq!(issued.load(Ordering::SeqCst), 1);
+        //токен отозван сервером: 401, новый токен и повтор запроса
+        issued.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(client.get().await.unwrap().text(), "Bearer t3");
+        assert_eq!(issued.load(Ordering::SeqCst), 3);
+
+        //токен истекающий раньше чем через 30 секунд запрашивается перед каждым запросом
+        let client = api(addr).with_token_provider(tokens(addr, "/short_token")).build().unwrap();
+        assert_eq!(client.get().await.unwrap().text(), "Bearer t4");
+        assert_eq!(client.get().await.unwrap().text(), "Bearer t5");
+
+        //ошибка получения токена возвращается из запроса
+        let client = api(addr).with_token_provider(tokens(addr, "/api")).build().unwrap();
+        assert!(matches!(client.get().await, Err(Error::JsonError { .. })));
+    }
+}
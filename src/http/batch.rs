@@ -0,0 +1,246 @@
+use std::{future::Future, sync::Arc, time::Duration};
+use futures::StreamExt;
+use hyper::Uri;
+use tokio::{sync::watch, time::Instant};
+use super::{builder::check_uri, ClientResponse, HyperClient};
+use crate::error::Error;
+
+///Выполнение пакета запросов с ограничением количества одновременных,
+///результаты возвращаются в порядке входных данных, ошибка одного запроса не прерывает остальные.
+///По истечении общего срока `with_deadline` или после `BatchCancel::cancel` выполняемые запросы прерываются,
+///а оставшиеся не начинаются и возвращают `Error::Timeout` или `Error::Cancelled`
+///```ignore
+///let batch = BatchExecutor::new(8).with_deadline(Duration::from_secs(300));
+///let cancel = batch.cancel_handle();
+///let responses = batch.fetch_all(&client, uris).await;
+///let documents = batch.run(ids, |id| client.clone().with_path(id).get_json::<Document>()).await;
+///```
+#[derive(Debug, Clone)]
+pub struct BatchExecutor
+{
+    concurrency: usize,
+    deadline: Option<Duration>,
+    cancel: BatchCancel
+}
+
+///Отмена пакета запросов `BatchExecutor`, действует на все пакеты исполнителя и его клонов
+#[derive(Debug, Clone)]
+pub struct BatchCancel(Arc<watch::Sender<bool>>);
+
+impl BatchCancel
+{
+    pub fn cancel(&self)
+    {
+        self.0.send_replace(true);
+    }
+    pub fn is_cancelled(&self) -> bool
+    {
+        *self.0.borrow()
+    }
+    async fn cancelled(&self)
+    {
+        //отправитель хранится в self, ошибки ожидания быть не может
+        let _ = self.0.subscribe().wait_for(|c| *c).await;
+    }
+}
+
+impl BatchExecutor
+{
+    ///не более `concurrency` запросов одновременно (не меньше одного)
+    pub fn new(concurrency: usize) -> Self
+    {
+        Self
+        {
+            concurrency: concurrency.max(1),
+            deadline: None,
+            cancel: BatchCancel(Arc::new(watch::channel(false).0))
+        }
+    }
+    ///общий срок выполнения пакета, отсчитывается от начала `run`
+    pub fn with_deadline(mut self, deadline: Duration) -> Self
+    {
+        self.deadline = Some(deadline);
+        self
+    }
+    pub fn cancel_handle(&self) -> BatchCancel
+    {
+        self.cancel.clone()
+    }
+    ///Выполнение `f` для каждого элемента `items`
+    pub async fn run<I, F, Fu, T>(&self, items: I, f: F) -> Vec<Result<T, Error>>
+    where I: IntoIterator,
+          F: Fn(I::Item) -> Fu,
+          Fu: Future<Output = Result<T, Error>>
+    {
+        let deadline = self.deadline.map(|d| Instant::now() + d);
+        let f = &f;
+        let mut results = futures::stream::iter(items.into_iter().enumerate())
+            .map(|(i, item)| async move
+            {
+                let expired = async
+                {
+                    match deadline
+                    {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending().await
+                    }
+                };
+                let result = tokio::select!
+                {
+                    biased;
+                    _ = self.cancel.cancelled() => Err(Error::Cancelled(format!("элемент {} пакета", i + 1))),
+                    _ = expired => Err(Error::Timeout(format!("элемент {} пакета", i + 1))),
+                    result = f(item) => result
+                };
+                (i, result)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await;
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+    ///GET запросы по адресам `uris` с настройками `client` (таймауты, повторы, заголовки, кэш),
+    ///адреса проверяются как в `HyperClientBuilder`, для некорректных возвращается `Error::ConfigError`
+    pub async fn fetch_all<I: IntoIterator<Item = Uri>>(&self, client: &HyperClient, uris: I) -> Vec<Result<ClientResponse, Error>>
+    {
+        let uris = uris.into_iter().collect::<Vec<_>>();
+        let results = self.run(uris.iter().cloned(), |uri|
+        {
+            let mut client = client.clone();
+            let checked = check_uri(&uri).map(|_| client.uri = uri);
+            async move
+            {
+                checked.map_err(Error::ConfigError)?;
+                client.get().await
+            }
+        }).await;
+        results
+            .into_iter()
+            .zip(&uris)
+            .map(|(result, uri)| result.map_err(|e| match e
+            {
+                Error::Cancelled(_) => Error::Cancelled(uri.to_string()),
+                Error::Timeout(_) => Error::Timeout(uri.to_string()),
+                e => e
+            }))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{net::SocketAddr, sync::{atomic::{AtomicUsize, Ordering}, Arc}, time::Duration};
+    use hyper::{body::Bytes, Request, Uri};
+    use hyper_util::rt::TokioIo;
+    use super::BatchExecutor;
+    use crate::{error::Error, http::{to_body, HyperClient}};
+
+    ///`/{ms}` отвечает через ms миллисекунд телом `ms`, второе значение - наибольшее количество одновременных запросов
+    async fn delay_server() -> (SocketAddr, Arc<AtomicUsize>)
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+        let result = max.clone();
+        tokio::spawn(async move
+        {
+            loop
+            {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (in_flight, max) = (in_flight.clone(), max.clone());
+                tokio::spawn(async move
+                {
+                    let service = hyper::service::service_fn(move |req: Request<hyper::body::Incoming>|
+                    {
+                        let (in_flight, max) = (in_flight.clone(), max.clone());
+                        async move
+                        {
+                            let ms = req.uri().path().trim_start_matches('/').to_owned();
+                            max.fetch_max(in_flight.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(ms.parse().unwrap_or_default())).await;
+                            in_flight.fetch_sub(1, Ordering::SeqCst);
+                            Ok::<_, hyper::Error>(hyper::Response::new(to_body(Bytes::from(ms))))
+                        }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        (addr, result)
+    }
+
+    fn uris(addr: SocketAddr, delays: &[u64]) -> Vec<Uri>
+    {
+        delays.iter().map(|ms| format!("http://{}/{}", addr, ms).parse().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_batch_fetch_all()
+    {
+        let (addr, max) = delay_server().await;
+        let client = HyperClient::builder(format!("http://{}/", addr)).with_retry_count(1).build().unwrap();
+        let mut list = uris(addr, &[150, 10, 100, 20, 50, 0]);
+        //недоступный адрес возвращает ошибку только для своего элемента
+        list.insert(2, "http://127.0.0.1:1/".parse().unwrap());
+        let results = BatchExecutor::new(3).fetch_all(&client, list).await;
+        assert_eq!(results.len(), 7);
+        assert!(results[2].is_err());
+        let bodies = results.iter().filter_map(|r| r.as_ref().ok()).map(|r| r.text()).collect::<Vec<_>>();
+        assert_eq!(bodies, vec!["150", "10", "100", "20", "50", "0"]);
+        assert_eq!(max.load(Ordering::SeqCst), 3);
+        let results = client.fetch_all(uris(addr, &[0, 0]), 0).await;
+        assert!(results.iter().all(|r| r.is_ok()));
+        //адреса без схемы и хоста или с другой схемой не запрашиваются
+        let list = vec!["/relative".parse().unwrap(), "ftp://example.com/".parse().unwrap(), uris(addr, &[0])[0].clone()];
+        let results = client.fetch_all(list, 3).await;
+        assert!(matches!(&results[0], Err(Error::ConfigError(e)) if e.contains("схема")));
+        assert!(matches!(&results[1], Err(Error::ConfigError(e)) if e.contains("ftp")));
+        assert_eq!(results[2].as_ref().unwrap().text(), "0");
+    }
+
+    #[tokio::test]
+    async fn test_batch_deadline_and_cancel()
+    {
+        let (addr, _) = delay_server().await;
+        let client = HyperClient::builder(format!("http://{}/", addr))
+            .with_read_timeout(Duration::from_secs(60))
+            .with_retry_count(1)
+            .build()
+            .unwrap();
+        //медленные запросы заведомо не укладываются в срок, быстрым запаса хватает и под нагрузкой
+        let batch = BatchExecutor::new(2).with_deadline(Duration::from_secs(1));
+        let list = uris(addr, &[0, 30000, 0, 0, 30000, 0]);
+        let results = batch.fetch_all(&client, list.clone()).await;
+        assert_eq!(results[0].as_ref().unwrap().text(), "0");
+        //один из двух потоков занят медленным запросом, второй успевает выполнить быстрые до следующего медленного
+        assert!(matches!(&results[1], Err(Error::Timeout(uri)) if *uri == list[1].to_string()));
+        assert!(results[2..4].iter().all(|r| r.is_ok()));
+        assert!(matches!(&results[4], Err(Error::Timeout(_))));
+        assert!(matches!(&results[5], Err(Error::Timeout(_))));
+
+        let batch = BatchExecutor::new(1);
+        let cancel = batch.cancel_handle();
+        let results = batch.run(0..4, |i|
+        {
+            let (client, cancel) = (client.clone().with_path(if i == 1 { "30000" } else { "0" }), cancel.clone());
+            async move
+            {
+                if i == 1
+                {
+                    tokio::spawn(async move
+                    {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        cancel.cancel();
+                    });
+                }
+                client.get().await.map(|r| r.text())
+            }
+        }).await;
+        assert!(cancel.is_cancelled());
+        assert_eq!(results[0].as_ref().unwrap(), "0");
+        assert!(results[1..].iter().all(|r| matches!(r, Err(Error::Cancelled(_)))));
+    }
+}
@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+use futures::{StreamExt, TryStreamExt};
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::{body::{Bytes, Frame}, header::HeaderValue};
+use serde::Serialize;
+use tokio::io::AsyncReadExt;
+use crate::error::Error;
+
+///Тело запроса отправляемое `HyperClient`
+pub(crate) type ClientBody = http_body_util::combinators::UnsyncBoxBody<Bytes, std::io::Error>;
+
+///Размер части файла читаемой за один раз
+const FILE_CHUNK: usize = 64 * 1024;
+
+///Тело запроса вместе с его Content-Type,
+///хранится целиком (файлы - путем и размером) чтобы запрос можно было повторить (куки, перенаправление, повтор после ошибки)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RequestBody
+{
+    parts: Vec<BodyPart>,
+    pub(crate) content_type: Option<HeaderValue>,
+    ///Content-Type задает формат тела (form, boundary у multipart) и заменяет заданный в заголовках клиента
+    pub(crate) replaces_content_type: bool
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum BodyPart
+{
+    Bytes(Bytes),
+    ///файл читается с диска при каждой отправке, размер зафиксирован при подготовке запроса
+    File(PathBuf, u64)
+}
+
+impl RequestBody
+{
+    pub(crate) fn new(parts: Vec<BodyPart>, content_type: HeaderValue) -> Self
+    {
+        Self { parts, content_type: Some(content_type), replaces_content_type: true }
+    }
+    ///Content-Type клиента (например `application/vnd.api+json`) имеет приоритет над `application/json`
+    pub(crate) fn json<B: Serialize + ?Sized>(body: &B) -> Result<Self, Error>
+    {
+        let body = Self::new(vec![BodyPart::Bytes(Bytes::from(serde_json::to_vec(body)?))], HeaderValue::from_static("application/json"));
+        Ok(Self { replaces_content_type: false, ..body })
+    }
+    ///`application/x-www-form-urlencoded`, ключи и значения кодируются как в параметрах адреса
+    pub(crate) fn form<S: AsRef<str>>(params: &[(S, S)]) -> Self
+    {
+        let form = params
+            .iter()
+            .map(|(k, v)| [super::encoding::encode(k.as_ref()), super::encoding::encode(v.as_ref())].join("="))
+            .collect::<Vec<_>>()
+            .join("&");
+        Self::new(vec![BodyPart::Bytes(Bytes::from(form))], HeaderValue::from_static("application/x-www-form-urlencoded"))
+    }
+    pub(crate) fn len(&self) -> u64
+    {
+        self.parts.iter().map(|p| match p
+        {
+            BodyPart::Bytes(b) => b.len() as u64,
+            BodyPart::File(_, len) => *len
+        }).sum()
+    }
+    ///тело для отправки, файлы открываются заново при каждой отправке
+    pub(crate) fn to_http_body(&self) -> ClientBody
+    {
+        match self.parts.as_slice()
+        {
+            [] => Full::new(Bytes::new()).map_err(|never| match never {}).boxed_unsync(),
+            [BodyPart::Bytes(b)] => Full::new(b.clone()).map_err(|never| match never {}).boxed_unsync(),
+            parts =>
+            {
+                let stream = futures::stream::iter(parts.to_vec())
+                    .flat_map(|part| match part
+                    {
+                        BodyPart::Bytes(b) => futures::stream::once(async move { Ok(b) }).boxed(),
+                        BodyPart::File(path, len) => file_stream(path, len).boxed()
+                    })
+                    .map_ok(Frame::data);
+                StreamBody::new(stream).boxed_unsync()
+            }
+        }
+    }
+}
+
+///чтение `len` байт файла частями, файл открывается при первом чтении
+fn file_stream(path: PathBuf, len: u64) -> impl futures::Stream<Item = Result<Bytes, std::io::Error>> + Send
+{
+    futures::stream::try_unfold((None::<tokio::fs::File>, path, len), |(file, path, remaining)| async move
+    {
+        if remaining == 0
+        {
+            return Ok(None);
+        }
+        let mut file = match file
+        {
+            Some(f) => f,
+            None => tokio::fs::File::open(&path).await?
+        };
+        let mut buf = vec![0u8; remaining.min(FILE_CHUNK as u64) as usize];
+        let n = file.read(&mut buf).await?;
+        if n == 0
+        {
+            let e = format!("файл {} изменился во время отправки, не хватает {} байт", path.display(), remaining);
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, e));
+        }
+        buf.truncate(n);
+        Ok(Some((Bytes::from(buf), (Some(file), path, remaining - n as u64))))
+    })
+}
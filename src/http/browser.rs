@@ -0,0 +1,310 @@
+use std::sync::{Arc, Mutex};
+use hyper::{header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_LANGUAGE, CONTENT_TYPE, REFERER, UPGRADE_INSECURE_REQUESTS, USER_AGENT}, Uri};
+use super::ClientResponse;
+
+///Набор заголовков браузера для сайтов, которые отвечают только "настоящим" браузерам.
+///Заголовки добавляются к запросам `HyperClient` если не заданы явно, кроме того клиент
+///подставляет `Referer` и `Sec-Fetch-Site` по предыдущей загруженной странице (ответу text/html на GET),
+///как при переходе по ссылке. Cookie сессии хранятся в `CookieJar` клиента, общем для его клонов
+///```ignore
+///let client = HyperClient::builder("http://pravo.gov.ru/proxy/ips/")
+///    .with_browser_profile(BrowserProfile::firefox())
+///    .build()?;
+///let list = client.get().await?;
+///let document = client.clone().with_path("?docbody=&nd=102027594").get().await?; //Referer: http://pravo.gov.ru/proxy/ips/
+///```
+#[derive(Debug, Clone)]
+pub struct BrowserProfile
+{
+    headers: HeaderMap,
+    ///`Sec-Fetch-Site` по предыдущей странице
+    fetch_site: bool,
+    ///`Referer` по предыдущей странице
+    auto_referer: bool
+}
+
+impl BrowserProfile
+{
+    ///Firefox 134 на Linux
+    pub fn firefox() -> Self
+    {
+        Self::preset(&[
+            ("user-agent", "Mozilla/5.0 (X11; Linux x86_64; rv:134.0) Gecko/20100101 Firefox/134.0"),
+            ("accept", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"),
+            ("accept-language", "ru-RU,ru;q=0.8,en-US;q=0.5,en;q=0.3"),
+            ("upgrade-insecure-requests", "1"),
+            ("sec-fetch-dest", "document"),
+            ("sec-fetch-mode", "navigate"),
+            ("sec-fetch-user", "?1"),
+            ("priority", "u=0, i")
+        ])
+    }
+    ///Chrome 131 на Linux
+    pub fn chrome() -> Self
+    {
+        Self::preset(&[
+            ("sec-ch-ua", "\"Google Chrome\";v=\"131\", \"Chromium\";v=\"131\", \"Not_A Brand\";v=\"24\""),
+            ("sec-ch-ua-mobile", "?0"),
+            ("sec-ch-ua-platform", "\"Linux\""),
+            ("upgrade-insecure-requests", "1"),
+            ("user-agent", "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36"),
+            ("accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7"),
+            ("sec-fetch-mode", "navigate"),
+            ("sec-fetch-user", "?1"),
+            ("sec-fetch-dest", "document"),
+            ("accept-language", "ru-RU,ru;q=0.9,en-US;q=0.8,en;q=0.7"),
+            ("priority", "u=0, i")
+        ])
+    }
+    ///Свой профиль: User-Agent, Accept и Accept-Language страницы, без `Sec-Fetch-*`,
+    ///остальные заголовки добавляются через `with_header`
+    pub fn custom<S: AsRef<str> + ToString>(user_agent: S) -> Self
+    {
+        let profile = Self { headers: HeaderMap::new(), fetch_site: false, auto_referer: true };
+        profile
+            .with_header(USER_AGENT, user_agent)
+            .with_header(ACCEPT, "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+            .with_header(ACCEPT_LANGUAGE, "ru-RU,ru;q=0.8,en-US;q=0.5,en;q=0.3")
+            .with_header(UPGRADE_INSECURE_REQUESTS, "1")
+    }
+    fn preset(headers: &[(&'static str, &'static str)]) -> Self
+    {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| (HeaderName::from_static(name), HeaderValue::from_static(value)))
+            .collect();
+        Self { headers, fetch_site: true, auto_referer: true }
+    }
+    ///заголовок с некорректным значением не добавляется, а ошибка пишется в лог
+    pub fn with_header<S: AsRef<str> + ToString>(mut self, name: HeaderName, value: S) -> Self
+    {
+        match HeaderValue::from_str(value.as_ref())
+        {
+            Ok(v) => { self.headers.insert(name, v); },
+            Err(e) => tracing::error!("Некорректное значение заголовка {}: {}", name, e)
+        }
+        self
+    }
+    pub fn without_header(mut self, name: HeaderName) -> Self
+    {
+        self.headers.remove(name);
+        self
+    }
+    pub fn with_accept_language<S: AsRef<str> + ToString>(self, languages: S) -> Self
+    {
+        self.with_header(ACCEPT_LANGUAGE, languages)
+    }
+    ///подстановка `Referer` по предыдущей странице, по умолчанию включена
+    pub fn with_auto_referer(mut self, enabled: bool) -> Self
+    {
+        self.auto_referer = enabled;
+        self
+    }
+    pub fn headers(&self) -> &HeaderMap
+    {
+        &self.headers
+    }
+}
+
+///Профиль вместе с адресом последней загруженной страницы, общий для клонов клиента
+#[derive(Debug, Clone)]
+pub(crate) struct BrowserSession
+{
+    profile: BrowserProfile,
+    page: Arc<Mutex<Option<Uri>>>
+}
+
+impl BrowserSession
+{
+    pub(crate) fn new(profile: BrowserProfile) -> Self
+    {
+        Self { profile, page: Arc::new(Mutex::new(None)) }
+    }
+    ///заголовки профиля для запроса на `uri`, заданные явно не заменяются
+    pub(crate) fn apply(&self, uri: &Uri, headers: &mut HeaderMap)
+    {
+        for (name, value) in &self.profile.headers
+        {
+            headers.entry(name).or_insert_with(|| value.clone());
+        }
+        let page = self.page.lock().unwrap().clone();
+        if self.profile.fetch_site
+        {
+            let site = match &page
+            {
+                None => "none",
+                Some(page) if same_origin(page, uri) => "same-origin",
+                Some(_) => "cross-site"
+            };
+            headers.entry("sec-fetch-site").or_insert(HeaderValue::from_static(site));
+        }
+        if self.profile.auto_referer && !headers.contains_key(REFERER)
+        {
+            if let Some(referer) = page.and_then(|page| referer(&page, uri))
+            {
+                headers.insert(REFERER, referer);
+            }
+        }
+    }
+    ///страницей считается успешный ответ HTML, ответы API и файлы не меняют Referer
+    pub(crate) fn navigated(&self, response: &ClientResponse)
+    {
+        let html = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("text/html") || ct.starts_with("application/xhtml+xml"));
+        if response.is_success() && html
+        {
+            *self.page.lock().unwrap() = Some(response.url().clone());
+        }
+    }
+}
+
+fn origin(uri: &Uri) -> (Option<&str>, Option<&str>, Option<u16>)
+{
+    let port = uri.port_u16().or(match uri.scheme_str()
+    {
+        Some("https") => Some(443),
+        Some("http") => Some(80),
+        _ => None
+    });
+    (uri.scheme_str(), uri.host(), port)
+}
+
+fn same_origin(a: &Uri, b: &Uri) -> bool
+{
+    let (a, b) = (origin(a), origin(b));
+    a.0 == b.0 && a.1.map(str::to_ascii_lowercase) == b.1.map(str::to_ascii_lowercase) && a.2 == b.2
+}
+
+///Referer по правилу браузеров по умолчанию `strict-origin-when-cross-origin`:
+///полный адрес для своего сайта, только origin для чужого и ничего при переходе с https на http
+fn referer(page: &Uri, uri: &Uri) -> Option<HeaderValue>
+{
+    if page.scheme_str() == Some("https") && uri.scheme_str() != Some("https")
+    {
+        return None;
+    }
+    let (scheme, host) = (page.scheme_str()?, page.host()?);
+    let origin = match page.port_u16()
+    {
+        Some(port) => format!("{}://{}:{}/", scheme, host, port),
+        None => format!("{}://{}/", scheme, host)
+    };
+    let referer = if same_origin(page, uri)
+    {
+        let path = page.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        [origin.trim_end_matches('/'), path].concat()
+    }
+    else
+    {
+        origin
+    };
+    HeaderValue::from_str(&referer).ok()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{collections::HashMap, net::SocketAddr};
+    use hyper::{body::Bytes, header::{CONTENT_TYPE, REFERER, SET_COOKIE, USER_AGENT}, HeaderMap, Request, Uri};
+    use hyper_util::rt::TokioIo;
+    use super::{BrowserProfile, BrowserSession};
+    use crate::http::{to_body, HyperClient};
+
+    ///отвечает заголовками запроса по строке `имя: значение`, `/api` - как JSON, остальные - как HTML с установкой cookie
+    async fn server() -> SocketAddr
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move
+        {
+            loop
+            {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move
+                {
+                    let service = hyper::service::service_fn(|req: Request<hyper::body::Incoming>| async move
+                    {
+                        let headers = req.headers().iter().map(|(n, v)| format!("{}: {}\n", n, v.to_str().unwrap())).collect::<String>();
+                        let mut response = hyper::Response::new(to_body(Bytes::from(headers)));
+                        if req.uri().path() == "/api"
+                        {
+                            response.headers_mut().insert(CONTENT_TYPE, "application/json".parse().unwrap());
+                        }
+                        else
+                        {
+                            response.headers_mut().insert(CONTENT_TYPE, "text/html; charset=utf-8".parse().unwrap());
+                            response.headers_mut().insert(SET_COOKIE, "session=1; Path=/".parse().unwrap());
+                        }
+                        Ok::<_, hyper::Error>(response)
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn get(client: &HyperClient) -> HashMap<String, String>
+    {
+        client.get().await.unwrap().text().lines().filter_map(|l| l.split_once(": ")).map(|(n, v)| (n.to_owned(), v.to_owned())).collect()
+    }
+
+    #[tokio::test]
+    async fn test_browser_profile_navigation()
+    {
+        let addr = server().await;
+        let client = HyperClient::builder(format!("http://{}/", addr)).with_retry_count(1).with_browser_profile(BrowserProfile::firefox()).build().unwrap();
+        let first = get(&client.clone().with_path("list")).await;
+        assert!(first["user-agent"].contains("Firefox"));
+        assert_eq!(first["sec-fetch-site"], "none");
+        assert_eq!(first["sec-fetch-mode"], "navigate");
+        assert!(!first.contains_key("referer"));
+        //запрос API не меняет предыдущую страницу
+        get(&client.clone().with_path("api")).await;
+        let second = get(&client.clone().with_path("document?id=1")).await;
+        assert_eq!(second["referer"], format!("http://{}/list", addr));
+        assert_eq!(second["sec-fetch-site"], "same-origin");
+        assert_eq!(second["cookie"], "session=1");
+        let third = get(&client.clone().with_path("other").with_header(USER_AGENT, "utilites").with_header(REFERER, "http://example.com/")).await;
+        assert_eq!(third["user-agent"], "utilites");
+        assert_eq!(third["referer"], "http://example.com/");
+
+        let chrome = HyperClient::builder(format!("http://{}/", addr))
+            .with_retry_count(1)
+            .with_browser_profile(BrowserProfile::chrome().with_accept_language("en-US").without_header("priority".parse().unwrap()))
+            .build()
+            .unwrap();
+        let headers = get(&chrome).await;
+        assert!(headers["user-agent"].contains("Chrome"));
+        assert_eq!(headers["accept-language"], "en-US");
+        assert_eq!(headers["sec-ch-ua-platform"], "\"Linux\"");
+        assert!(!headers.contains_key("priority"));
+    }
+
+    #[test]
+    fn test_browser_referer_policy()
+    {
+        let session = BrowserSession::new(BrowserProfile::custom("utilites").with_auto_referer(true));
+        let apply = |page: &str, uri: &str|
+        {
+            *session.page.lock().unwrap() = Some(page.parse().unwrap());
+            let mut headers = HeaderMap::new();
+            session.apply(&uri.parse::<Uri>().unwrap(), &mut headers);
+            headers.get(REFERER).map(|r| r.to_str().unwrap().to_owned())
+        };
+        assert_eq!(apply("https://example.com/a/b?c=1", "https://EXAMPLE.com:443/d").as_deref(), Some("https://example.com/a/b?c=1"));
+        assert_eq!(apply("https://example.com/a/b?c=1", "https://other.com/d").as_deref(), Some("https://example.com/"));
+        assert_eq!(apply("http://example.com:8080/a", "http://other.com/d").as_deref(), Some("http://example.com:8080/"));
+        assert_eq!(apply("https://example.com/a", "http://example.com/a"), None);
+        let session = BrowserSession::new(BrowserProfile::custom("utilites").with_auto_referer(false));
+        *session.page.lock().unwrap() = Some("http://example.com/a".parse().unwrap());
+        let mut headers = HeaderMap::new();
+        session.apply(&"http://example.com/b".parse().unwrap(), &mut headers);
+        assert!(!headers.contains_key(REFERER));
+        assert!(!headers.contains_key("sec-fetch-site"));
+        assert_eq!(headers[USER_AGENT], "utilites");
+    }
+}
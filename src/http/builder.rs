@@ -0,0 +1,452 @@
+use std::{fmt::Display, time::Duration};
+use hyper::{header::{HeaderName, HeaderValue, USER_AGENT}, Uri};
+use super::{auth::Auth, merge_headers, BrowserProfile, CookieJar, HttpCache, HttpVersionPolicy, HyperClient, Middleware, Proxy, RedirectPolicy, RetryPolicy, TokenProvider};
+use crate::error::Error;
+
+///Настройка `HyperClient`
+///ошибки в адресе, заголовках и таймаутах накапливаются и возвращаются из `build` как `Error::ConfigError`
+///```ignore
+///let client = HyperClient::builder("https://example.com/api")
+///    .with_user_agent("utilites/0.1")
+///    .with_connect_timeout(Duration::from_secs(2))
+///    .with_read_timeout(Duration::from_secs(10))
+///    .with_total_timeout(Duration::from_secs(60))
+///    .with_backoff(Duration::from_millis(100), Duration::from_millis(400))
+///    .build()?;
+///```
+#[derive(Debug, Clone)]
+pub struct HyperClientBuilder
+{
+    client: HyperClient,
+    errors: Vec<String>
+}
+
+impl HyperClientBuilder
+{
+    ///настройки по умолчанию: таймаут попытки и задержка между повторами от 5 до 30 секунд, 7 попыток
+    pub fn new<U>(uri: U) -> Self
+    where U: TryInto<Uri>, U::Error: Display
+    {
+        let mut errors = Vec::new();
+        let uri = match uri.try_into()
+        {
+            Ok(uri) =>
+            {
+                if let Err(e) = check_uri(&uri)
+                {
+                    errors.push(e);
+                }
+                uri
+            },
+            Err(e) =>
+            {
+                errors.push(format!("некорректный адрес: {}", e));
+                Uri::default()
+            }
+        };
+        Self { client: HyperClient::with_defaults(uri), errors }
+    }
+    ///добавление пути к адресу, недопустимые в адресе символы кодируются
+    pub fn with_path<P: AsRef<str>>(mut self, path: P) -> Self
+    {
+        match super::join_path(&self.client.uri, path.as_ref())
+        {
+            Ok(uri) => self.client.uri = uri,
+            Err(e) => self.errors.push(format!("некорректный путь `{}`: {}", path.as_ref(), e))
+        }
+        self
+    }
+    ///заголовок заменяет ранее заданный с тем же именем
+    pub fn with_header<K, V>(mut self, name: K, value: V) -> Self
+    where K: TryInto<HeaderName>, K::Error: Display,
+          V: TryInto<HeaderValue>, V::Error: Display
+    {
+        if let Some((name, value)) = self.header(name, value)
+        {
+            self.client.headers.insert(name, value);
+        }
+        self
+    }
+    ///заголовки добавляются к уже заданным, одноименные заменяются,
+    ///несколько значений одного заголовка в `headers` сохраняются все
+    pub fn with_headers<K, V>(mut self, headers: Vec<(K, V)>) -> Self
+    where K: TryInto<HeaderName>, K::Error: Display,
+          V: TryInto<HeaderValue>, V::Error: Display
+    {
+        let headers = headers.into_iter().filter_map(|(name, value)| self.header(name, value)).collect::<Vec<_>>();
+        merge_headers(&mut self.client.headers, headers);
+        self
+    }
+    pub fn with_user_agent<V>(self, user_agent: V) -> Self
+    where V: TryInto<HeaderValue>, V::Error: Display
+    {
+        self.with_header(USER_AGENT, user_agent)
+    }
+    ///заголовок `Authorization: Basic` для всех запросов, заданный через `with_header` заголовок имеет приоритет
+    pub fn with_basic_auth<U: AsRef<str>, P: AsRef<str>>(mut self, user: U, password: P) -> Self
+    {
+        match Auth::basic(user.as_ref(), password.as_ref())
+        {
+            Ok(a) => self.client.auth = a,
+            Err(e) => self.errors.push(config_message(e))
+        }
+        self
+    }
+    ///заголовок `Authorization: Bearer` для всех запросов
+    pub fn with_bearer_auth<T: AsRef<str>>(mut self, token: T) -> Self
+    {
+        match Auth::bearer(token.as_ref())
+        {
+            Ok(a) => self.client.auth = a,
+            Err(e) => self.errors.push(config_message(e))
+        }
+        self
+    }
+    ///токен запрашивается у `provider` перед каждой попыткой (например `OAuth2ClientCredentials`),
+    ///если сервер ответил 401 токен запрашивается заново и запрос один раз повторяется
+    pub fn with_token_provider<T: TokenProvider + 'static>(mut self, provider: T) -> Self
+    {
+        self.client = self.client.with_token_provider(provider);
+        self
+    }
+    ///добавление обработчика в конец цепочки `Middleware`
+    pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self
+    {
+        self.client = self.client.with_middleware(middleware);
+        self
+    }
+    ///кэш ответов на GET запросы с проверкой по ETag и Last-Modified
+    pub fn with_cache(mut self, cache: HttpCache) -> Self
+    {
+        self.client = self.client.with_cache(cache);
+        self
+    }
+    ///заголовки браузера (`BrowserProfile::firefox`, `BrowserProfile::chrome`) и Referer по предыдущей странице
+    pub fn with_browser_profile(mut self, profile: BrowserProfile) -> Self
+    {
+        self.client = self.client.with_browser_profile(profile);
+        self
+    }
+    ///время на установку tcp соединения, по умолчанию не ограничено
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self
+    {
+        self.client.connect_timeout = Some(timeout);
+        self
+    }
+    ///время одной попытки, для буферизованного ответа до получения всего тела,
+    ///для потокового ответа до получения заголовков и далее на ожидание каждой части тела
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self
+    {
+        self.client.read_timeout = (timeout, timeout);
+        self
+    }
+    ///ограничение времени запроса вместе со всеми повторами и задержками между ними,
+    ///при превышении возвращается `Error::Timeout`, по умолчанию не ограничено
+    pub fn with_total_timeout(mut self, timeout: Duration) -> Self
+    {
+        self.client.total_timeout = Some(timeout);
+        self
+    }
+    ///задержка перед повтором, выбирается случайно из диапазона `from..to`
+    ///если сервер вернул Retry-After используется задержка из заголовка
+    pub fn with_backoff(mut self, from: Duration, to: Duration) -> Self
+    {
+        self.client.backoff = (from, to);
+        self
+    }
+    ///общее количество попыток, 0 - без ограничения
+    pub fn with_retry_count(mut self, count: u8) -> Self
+    {
+        self.client.retry_count = count;
+        self
+    }
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self
+    {
+        self.client = self.client.with_retry_policy(policy);
+        self
+    }
+    pub fn with_redirect_policy(mut self, policy: RedirectPolicy) -> Self
+    {
+        self.client = self.client.with_redirect_policy(policy);
+        self
+    }
+    pub fn with_cookie_jar(mut self, jar: CookieJar) -> Self
+    {
+        self.client = self.client.with_cookie_jar(jar);
+        self
+    }
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self
+    {
+        self.client = self.client.with_pool_idle_timeout(timeout);
+        self
+    }
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self
+    {
+        self.client = self.client.with_pool_max_idle_per_host(max);
+        self
+    }
+    ///ограничение частоты запросов к одному хосту: не больше `per_second` в секунду в среднем,
+    ///`burst` запросов подряд без ожидания, проверяется перед каждой попыткой и каждым перенаправлением,
+    ///ограничение общее для всех клонов клиента, ожидание токена ограничено только общим таймаутом
+    pub fn with_rate_limit(mut self, per_second: f64, burst: u32) -> Self
+    {
+        self.client.limiter.set_rate(per_second, burst);
+        self
+    }
+    ///максимум одновременных запросов к одному хосту для всех клонов клиента, остальные ждут в очереди
+    ///(время в очереди не входит в таймаут попытки),
+    ///запрос занимает место до получения тела ответа (для потокового ответа - до получения заголовков)
+    pub fn with_max_in_flight_per_host(mut self, max: usize) -> Self
+    {
+        self.client.limiter.set_max_in_flight(max);
+        self
+    }
+    ///версия протокола HTTP, по умолчанию HTTP/2 используется если сервер выбрал его через ALPN,
+    ///выбранная версия возвращается в `ClientResponse::version`
+    pub fn with_http_version(mut self, version: HttpVersionPolicy) -> Self
+    {
+        self.client.http_version = version;
+        self
+    }
+    ///прокси для всех запросов: `http://[user:password@]host[:port]` (для https туннель через CONNECT),
+    ///`socks5://...` или `socks5h://...` (имя сервера разрешает прокси)
+    pub fn with_proxy<S: AsRef<str>>(self, url: S) -> Self
+    {
+        self.with_http_proxy(url.as_ref()).with_https_proxy(url.as_ref())
+    }
+    ///прокси только для адресов http
+    pub fn with_http_proxy<S: AsRef<str>>(mut self, url: S) -> Self
+    {
+        match Proxy::parse(url.as_ref())
+        {
+            Ok(proxy) => self.client.proxy.set_http(proxy),
+            Err(e) => self.errors.push(config_message(e))
+        }
+        self
+    }
+    ///прокси только для адресов https
+    pub fn with_https_proxy<S: AsRef<str>>(mut self, url: S) -> Self
+    {
+        match Proxy::parse(url.as_ref())
+        {
+            Ok(proxy) => self.client.proxy.set_https(proxy),
+            Err(e) => self.errors.push(config_message(e))
+        }
+        self
+    }
+    ///basic авторизация на прокси, если пользователь не указан в адресе прокси
+    pub fn with_proxy_basic_auth<U: Into<String>, P: Into<String>>(mut self, user: U, password: P) -> Self
+    {
+        self.client.proxy.set_credentials(user.into(), password.into());
+        self
+    }
+    ///хосты к которым запросы идут без прокси, список через запятую как в `NO_PROXY`:
+    ///`example.com` (вместе с поддоменами), `.example.com`, `127.0.0.1`, `*` - все хосты
+    pub fn with_no_proxy<S: AsRef<str>>(mut self, hosts: S) -> Self
+    {
+        self.client.proxy.add_no_proxy(hosts.as_ref());
+        self
+    }
+    ///прокси из переменных окружения `http_proxy` (только в нижнем регистре), `HTTPS_PROXY`, `ALL_PROXY` и `NO_PROXY` (или в нижнем регистре),
+    ///заданные явно через `with_proxy` прокси имеют приоритет
+    pub fn with_env_proxy(mut self) -> Self
+    {
+        if let Err(e) = self.client.proxy.load_env(|name| std::env::var(name).ok())
+        {
+            self.errors.push(config_message(e));
+        }
+        self
+    }
+    ///дополнительные корневые сертификаты в формате PEM, например сертификат внутреннего CA
+    pub fn with_root_certificates_pem<P: AsRef<[u8]>>(mut self, pem: P) -> Self
+    {
+        if let Err(e) = self.client.tls.add_roots_pem(pem.as_ref())
+        {
+            self.errors.push(e);
+        }
+        self
+    }
+    ///использовать системные корневые сертификаты, по умолчанию включено
+    pub fn with_native_roots(mut self, enabled: bool) -> Self
+    {
+        self.client.tls.set_native_roots(enabled);
+        self
+    }
+    ///сертификат (с цепочкой) и закрытый ключ клиента в формате PEM для взаимной аутентификации TLS
+    pub fn with_client_certificate_pem<C: AsRef<[u8]>, K: AsRef<[u8]>>(mut self, cert: C, key: K) -> Self
+    {
+        if let Err(e) = self.client.tls.set_client_auth_pem(cert.as_ref(), key.as_ref())
+        {
+            self.errors.push(e);
+        }
+        self
+    }
+    ///закрепление ключа сервера: sha256 SubjectPublicKeyInfo сертификата сервера или одного из промежуточных,
+    ///можно указать несколько значений (например текущий и следующий ключ), хэш из сертификата считает `spki_sha256`
+    pub fn with_pinned_spki_sha256(mut self, hash: [u8; 32]) -> Self
+    {
+        self.client.tls.add_pin(hash);
+        self
+    }
+    ///отключение проверки сертификата сервера, только для тестовых стендов!
+    ///закрепленные через `with_pinned_spki_sha256` ключи при этом проверяются
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self
+    {
+        self.client.tls.set_accept_invalid_certs(accept);
+        self
+    }
+    #[cfg(feature="decompression")]
+    pub fn with_decompression(mut self, enabled: bool) -> Self
+    {
+        self.client = self.client.with_decompression(enabled);
+        self
+    }
+    ///проверка настроек и создание клиента
+    pub fn build(mut self) -> Result<HyperClient, Error>
+    {
+        let client = &self.client;
+        if client.read_timeout.0.is_zero()
+        {
+            self.errors.push("таймаут попытки должен быть больше нуля".to_owned());
+        }
+        if client.connect_timeout.is_some_and(|t| t.is_zero())
+        {
+            self.errors.push("таймаут подключения должен быть больше нуля".to_owned());
+        }
+        if client.total_timeout.is_some_and(|t| t.is_zero())
+        {
+            self.errors.push("общий таймаут должен быть больше нуля".to_owned());
+        }
+        if client.backoff.0 > client.backoff.1
+        {
+            self.errors.push(format!("минимальная задержка между повторами {:?} больше максимальной {:?}", client.backoff.0, client.backoff.1));
+        }
+        if let Some(status) = client.retry.statuses().iter().find(|s| !s.is_client_error() && !s.is_server_error())
+        {
+            self.errors.push(format!("повтор запроса возможен только для статусов 4xx и 5xx, указан {}", status));
+        }
+        if let Err(e) = client.limiter.validate()
+        {
+            self.errors.push(e);
+        }
+        if let Err(e) = client.tls.validate()
+        {
+            self.errors.push(e);
+        }
+        if self.errors.is_empty()
+        {
+            Ok(self.client)
+        }
+        else
+        {
+            Err(Error::ConfigError(self.errors.join("; ")))
+        }
+    }
+    fn header<K, V>(&mut self, name: K, value: V) -> Option<(HeaderName, HeaderValue)>
+    where K: TryInto<HeaderName>, K::Error: Display,
+          V: TryInto<HeaderValue>, V::Error: Display
+    {
+        let name = match name.try_into()
+        {
+            Ok(n) => n,
+            Err(e) =>
+            {
+                self.errors.push(format!("некорректное имя заголовка: {}", e));
+                return None;
+            }
+        };
+        match value.try_into()
+        {
+            Ok(v) => Some((name, v)),
+            Err(e) =>
+            {
+                self.errors.push(format!("некорректное значение заголовка {}: {}", name, e));
+                None
+            }
+        }
+    }
+}
+
+///текст ошибки без префикса `ConfigError`, он добавляется в `build`
+fn config_message(e: Error) -> String
+{
+    match e
+    {
+        Error::ConfigError(message) => message,
+        e => e.to_string()
+    }
+}
+
+///адрес должен быть абсолютным http или https
+pub(super) fn check_uri(uri: &Uri) -> Result<(), String>
+{
+    match uri.scheme_str()
+    {
+        Some("http") | Some("https") => (),
+        Some(s) => return Err(format!("неподдерживаемая схема `{}` в адресе {}", s, uri)),
+        None => return Err(format!("в адресе {} не указана схема", uri))
+    }
+    if uri.host().is_none_or(|h| h.is_empty())
+    {
+        return Err(format!("в адресе {} не указан хост", uri));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::time::Duration;
+    use hyper::{header::{ACCEPT, ACCEPT_LANGUAGE, USER_AGENT}, StatusCode};
+    use super::HyperClientBuilder;
+    use crate::{error::Error, http::RetryPolicy};
+
+    fn config_error(builder: HyperClientBuilder) -> String
+    {
+        match builder.build()
+        {
+            Err(Error::ConfigError(e)) => e,
+            other => panic!("ожидалась ошибка настройки, получено {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_builder_validation()
+    {
+        assert!(config_error(HyperClientBuilder::new("не адрес")).contains("некорректный адрес"));
+        assert!(config_error(HyperClientBuilder::new("/relative")).contains("схема"));
+        assert!(config_error(HyperClientBuilder::new("ftp://example.com")).contains("ftp"));
+        let builder = || HyperClientBuilder::new("http://example.com");
+        assert!(config_error(builder().with_header("x-test", "перевод\nстроки")).contains("x-test"));
+        assert!(config_error(builder().with_header("bad header", "1")).contains("имя заголовка"));
+        assert!(config_error(builder().with_read_timeout(Duration::ZERO)).contains("таймаут попытки"));
+        assert!(config_error(builder().with_backoff(Duration::from_secs(2), Duration::from_secs(1))).contains("задержка"));
+        let policy = RetryPolicy::default().with_statuses(vec![StatusCode::OK]);
+        assert!(config_error(builder().with_retry_policy(policy)).contains("200"));
+        assert!(config_error(builder().with_proxy("ftp://proxy.local")).starts_with("неподдерживаемая схема прокси"));
+        //все ошибки возвращаются вместе
+        let errors = config_error(builder().with_connect_timeout(Duration::ZERO).with_total_timeout(Duration::ZERO));
+        assert!(errors.contains("подключения") && errors.contains("общий"));
+    }
+
+    #[test]
+    fn test_builder_headers_and_path()
+    {
+        let client = HyperClientBuilder::new("http://example.com/api")
+            .with_path("/документы list")
+            .with_user_agent("utilites")
+            .with_headers(vec![(ACCEPT, "text/html")])
+            .with_headers(vec![(ACCEPT, "application/json"), (ACCEPT, "text/plain"), (USER_AGENT, "utilites/0.2")])
+            .with_headers(vec![(ACCEPT_LANGUAGE, "ru")])
+            .build()
+            .unwrap();
+        assert_eq!(client.get_uri(), "http://example.com/api/%D0%B4%D0%BE%D0%BA%D1%83%D0%BC%D0%B5%D0%BD%D1%82%D1%8B%20list");
+        //одноименные заголовки заменяются, значения из одного вызова сохраняются все, остальные заголовки не удаляются
+        assert_eq!(client.headers.get_all(ACCEPT).iter().collect::<Vec<_>>(), vec!["application/json", "text/plain"]);
+        assert_eq!(client.headers.get_all(USER_AGENT).iter().collect::<Vec<_>>(), vec!["utilites/0.2"]);
+        assert_eq!(client.headers.get(ACCEPT_LANGUAGE).unwrap(), "ru");
+        let client = client.with_headers(vec![(USER_AGENT, "utilites/0.3")]);
+        assert_eq!(client.headers.get_all(USER_AGENT).iter().collect::<Vec<_>>(), vec!["utilites/0.3"]);
+        assert_eq!(client.headers.get_all(ACCEPT).iter().count(), 2);
+    }
+}
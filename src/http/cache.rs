@@ -0,0 +1,640 @@
+use std::{collections::HashMap, future::Future, path::{Path, PathBuf}, sync::{Arc, Mutex}, time::{Instant, SystemTime, UNIX_EPOCH}};
+use hyper::{body::Bytes, header::{HeaderMap, HeaderName, HeaderValue, AGE, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH, DATE, ETAG, EXPIRES, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, SET_COOKIE, TRANSFER_ENCODING, VARY}, Method, Request, StatusCode, Version};
+use serde::{Deserialize, Serialize};
+use super::{body::RequestBody, cookie::parse_cookie_date, ClientResponse};
+use crate::error::Error;
+
+///Кэш ответов на GET запросы `HyperClient` (частный кэш по RFC 9111).
+///Сохраняются ответы 200 с `ETag`, `Last-Modified` или сроком свежести (`Cache-Control: max-age`, `Expires`),
+///кроме `Cache-Control: no-store`. Свежий ответ возвращается без запроса к серверу, устаревший проверяется
+///условным запросом с `If-None-Match`/`If-Modified-Since` и на ответ 304 возвращается сохраненное тело.
+///Ключ - метод и адрес запроса, для ответов с `Vary` еще и значения перечисленных в нем заголовков запроса.
+///Успешный POST, PUT, PATCH или DELETE на адрес удаляет сохраненный для него ответ.
+///Ответ на запрос с `Authorization` сохраняется только с `Cache-Control: public`, `s-maxage` или `must-revalidate`
+///(RFC 9111 3.5), поэтому один кэш можно передавать клиентам с разными учетными данными
+///```ignore
+///let client = HyperClient::builder("https://example.com/docs/1.html")
+///    .with_cache(HttpCache::disk("/var/cache/docs"))
+///    .build()?;
+///```
+#[derive(Debug, Clone)]
+pub struct HttpCache
+{
+    store: Store
+}
+
+///хранилище общее для всех клонов кэша
+#[derive(Debug, Clone)]
+enum Store
+{
+    Memory(Arc<Mutex<MemoryStore>>),
+    Disk(Arc<PathBuf>)
+}
+
+#[derive(Debug)]
+struct MemoryStore
+{
+    capacity: usize,
+    ///счетчик обращений, при переполнении удаляется адрес с наименьшим значением
+    tick: u64,
+    entries: HashMap<String, (u64, Vec<CachedEntry>)>
+}
+
+///Сохраненный ответ, для одного ключа хранится по варианту на каждое сочетание значений заголовков из Vary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedEntry
+{
+    ///заголовки запроса из Vary ответа и их значения в запросе
+    vary: Vec<(String, Option<String>)>,
+    status: u16,
+    version: String,
+    headers: Vec<(String, String)>,
+    url: String,
+    redirects: Vec<String>,
+    ///время получения или последней проверки ответа, секунды unix
+    stored: u64,
+    ///имя файла тела в каталоге кэша
+    body_file: String,
+    #[serde(skip)]
+    body: Bytes
+}
+
+///директивы Cache-Control которые учитывает кэш
+#[derive(Debug, Default)]
+struct CacheControl
+{
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<u64>,
+    ///`public`, `s-maxage` или `must-revalidate`: ответ на запрос с авторизацией можно сохранить
+    shared: bool
+}
+
+impl CacheControl
+{
+    fn parse(headers: &HeaderMap) -> Self
+    {
+        let mut cc = Self::default();
+        let directives = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','));
+        for directive in directives
+        {
+            let (name, value) = directive.split_once('=').unwrap_or((directive, ""));
+            match name.trim().to_ascii_lowercase().as_str()
+            {
+                "no-store" => cc.no_store = true,
+                "no-cache" => cc.no_cache = true,
+                "max-age" => cc.max_age = value.trim().trim_matches('"').parse().ok(),
+                "public" | "s-maxage" | "must-revalidate" => cc.shared = true,
+                _ => ()
+            }
+        }
+        cc
+    }
+}
+
+impl HttpCache
+{
+    ///хранение в памяти не более `capacity` адресов, при переполнении удаляется дольше всех не запрашивавшийся
+    pub fn memory(capacity: usize) -> Self
+    {
+        let store = MemoryStore { capacity, tick: 0, entries: HashMap::new() };
+        Self { store: Store::Memory(Arc::new(Mutex::new(store))) }
+    }
+    ///хранение в каталоге `dir`: описания ответов в файлах `.json`, тела в `.body`,
+    ///каталог создается при первой записи, размер не ограничивается
+    pub fn disk<P: Into<PathBuf>>(dir: P) -> Self
+    {
+        Self { store: Store::Disk(Arc::new(dir.into())) }
+    }
+    ///удаление всех сохраненных ответов, для дискового кэша удаляется каталог
+    pub async fn clear(&self) -> Result<(), Error>
+    {
+        match &self.store
+        {
+            Store::Memory(m) => m.lock().unwrap().entries.clear(),
+            Store::Disk(dir) => match tokio::fs::remove_dir_all(dir.as_path()).await
+            {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => ()
+            }
+        }
+        Ok(())
+    }
+    ///Запрос через кэш, `send` выполняет запрос к серверу,
+    ///`authorized` - к запросу будет добавлен `Authorization` (в том числе клиентом после кэша)
+    pub(crate) async fn send<F, Fu>(&self, mut req: Request<RequestBody>, authorized: bool, send: F) -> Result<ClientResponse, Error>
+    where F: FnOnce(Request<RequestBody>) -> Fu,
+          Fu: Future<Output = Result<ClientResponse, Error>>
+    {
+        if req.method() != Method::GET
+        {
+            let uri = req.uri().clone();
+            let safe = matches!(*req.method(), Method::HEAD | Method::OPTIONS | Method::TRACE);
+            let response = send(req).await?;
+            if !safe && response.is_success()
+            {
+                self.remove(&format!("{} {}", Method::GET, uri)).await;
+            }
+            return Ok(response);
+        }
+        let request_cc = CacheControl::parse(req.headers());
+        if request_cc.no_store
+        {
+            return send(req).await;
+        }
+        let key = format!("{} {}", req.method(), req.uri());
+        let started = Instant::now();
+        let cached = self.load(&key).await.into_iter().find(|e| e.matches(req.headers()));
+        let mut conditional = false;
+        if let Some(entry) = &cached
+        {
+            if !request_cc.no_cache && request_cc.max_age != Some(0) && entry.is_fresh()
+            {
+                tracing::debug!("Ответ на {} взят из кэша", key);
+                return Ok(entry.to_response(started.elapsed()));
+            }
+            conditional = entry.add_conditions(req.headers_mut());
+        }
+        let request_headers = req.headers().clone();
+        let response = send(req).await?;
+        match cached
+        {
+            Some(mut entry) if conditional && response.status == StatusCode::NOT_MODIFIED =>
+            {
+                tracing::debug!("Сохраненный ответ на {} не изменился", key);
+                entry.revalidate(&response.headers);
+                let revalidated = entry.to_response(response.elapsed);
+                self.save(&key, entry).await;
+                Ok(revalidated)
+            },
+            _ =>
+            {
+                if let Some(entry) = CachedEntry::new(&key, &request_headers, authorized, &response)
+                {
+                    self.save(&key, entry).await;
+                }
+                Ok(response)
+            }
+        }
+    }
+    ///ошибки чтения кэша не прерывают запрос, он просто выполняется без кэша
+    async fn load(&self, key: &str) -> Vec<CachedEntry>
+    {
+        match &self.store
+        {
+            Store::Memory(m) =>
+            {
+                let mut store = m.lock().unwrap();
+                store.tick += 1;
+                let tick = store.tick;
+                store.entries.get_mut(key).map(|(used, entries)| { *used = tick; entries.clone() }).unwrap_or_default()
+            },
+            Store::Disk(dir) =>
+            {
+                let mut entries = Self::read_index(dir, key).await;
+                for entry in entries.iter_mut()
+                {
+                    match tokio::fs::read(dir.join(&entry.body_file)).await
+                    {
+                        Ok(body) => entry.body = Bytes::from(body),
+                        Err(e) =>
+                        {
+                            tracing::warn!("Ошибка чтения сохраненного ответа на {}: {}", key, e);
+                            entry.body_file.clear();
+                        }
+                    }
+                }
+                entries.retain(|e| !e.body_file.is_empty());
+                entries
+            }
+        }
+    }
+    async fn read_index(dir: &Path, key: &str) -> Vec<CachedEntry>
+    {
+        let index = dir.join(format!("{}.json", hash(key)));
+        match tokio::fs::read(&index).await
+        {
+            Ok(json) => serde_json::from_slice(&json).unwrap_or_else(|e|
+            {
+                tracing::warn!("Ошибка разбора {}: {}", index.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new()
+        }
+    }
+    ///вариант с теми же значениями заголовков из Vary заменяется
+    async fn save(&self, key: &str, entry: CachedEntry)
+    {
+        match &self.store
+        {
+            Store::Memory(m) =>
+            {
+                let mut store = m.lock().unwrap();
+                store.tick += 1;
+                let tick = store.tick;
+                let (used, entries) = store.entries.entry(key.to_owned()).or_default();
+                *used = tick;
+                entries.retain(|e| e.vary != entry.vary);
+                entries.push(entry);
+                while store.entries.len() > store.capacity
+                {
+                    let oldest = store.entries.iter().min_by_key(|(_, (used, _))| *used).map(|(k, _)| k.clone());
+                    if let Some(oldest) = oldest
+                    {
+                        store.entries.remove(&oldest);
+                    }
+                }
+            },
+            Store::Disk(dir) =>
+            {
+                let mut entries = Self::read_index(dir, key).await;
+                entries.retain(|e| e.vary != entry.vary);
+                let body = entry.body.clone();
+                let body_file = entry.body_file.clone();
+                entries.push(entry);
+                let result = async
+                {
+                    tokio::fs::create_dir_all(dir.as_path()).await?;
+                    write_file(&dir.join(body_file), &body).await?;
+                    write_file(&dir.join(format!("{}.json", hash(key))), &serde_json::to_vec(&entries)?).await
+                };
+                if let Err(e) = result.await
+                {
+                    tracing::warn!("Ошибка сохранения ответа на {} в {}: {}", key, dir.display(), e);
+                }
+            }
+        }
+    }
+    async fn remove(&self, key: &str)
+    {
+        match &self.store
+        {
+            Store::Memory(m) =>
+            {
+                m.lock().unwrap().entries.remove(key);
+            },
+            Store::Disk(dir) =>
+            {
+                for entry in Self::read_index(dir, key).await
+                {
+                    let _ = tokio::fs::remove_file(dir.join(entry.body_file)).await;
+                }
+                let _ = tokio::fs::remove_file(dir.join(format!("{}.json", hash(key)))).await;
+            }
+        }
+    }
+}
+
+impl CachedEntry
+{
+    ///None если ответ нельзя или бесполезно сохранять
+    fn new(key: &str, request_headers: &HeaderMap, authorized: bool, response: &ClientResponse) -> Option<Self>
+    {
+        let cc = CacheControl::parse(&response.headers);
+        if response.status != StatusCode::OK || cc.no_store || (authorized && !cc.shared)
+        {
+            return None;
+        }
+        let has_validator = response.headers.contains_key(ETAG) || response.headers.contains_key(LAST_MODIFIED);
+        if !has_validator && cc.max_age.is_none() && !response.headers.contains_key(EXPIRES)
+        {
+            return None;
+        }
+        let vary = response.headers
+            .get_all(VARY)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+        if vary.iter().any(|name| name == "*")
+        {
+            return None;
+        }
+        let vary = vary.into_iter().map(|name| { let value = header_value(request_headers, &name); (name, value) }).collect::<Vec<_>>();
+        let variant = vary.iter().map(|(n, v)| format!("\n{}: {}", n, v.as_deref().unwrap_or_default())).collect::<String>();
+        let headers = response.headers
+            .iter()
+            //куки уже сохранены клиентом при получении ответа
+            .filter(|(name, _)| *name != SET_COOKIE)
+            .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+            .collect();
+        Some(Self
+        {
+            vary,
+            status: response.status.as_u16(),
+            version: format!("{:?}", response.version),
+            headers,
+            url: response.url.to_string(),
+            redirects: response.redirects.iter().map(|r| r.to_string()).collect(),
+            stored: now(),
+            body_file: format!("{}.body", hash(&[key, &variant].concat())),
+            body: response.body.clone()
+        })
+    }
+    fn matches(&self, request_headers: &HeaderMap) -> bool
+    {
+        self.vary.iter().all(|(name, value)| header_value(request_headers, name) == *value)
+    }
+    fn header_map(&self) -> HeaderMap
+    {
+        self.headers
+            .iter()
+            .filter_map(|(name, value)| Some((HeaderName::from_bytes(name.as_bytes()).ok()?, HeaderValue::from_str(value).ok()?)))
+            .collect()
+    }
+    ///срок свежести из max-age или Expires больше возраста ответа
+    fn is_fresh(&self) -> bool
+    {
+        let headers = self.header_map();
+        let cc = CacheControl::parse(&headers);
+        if cc.no_cache
+        {
+            return false;
+        }
+        let date = |name| headers.get(name).and_then(|v: &HeaderValue| v.to_str().ok()).and_then(parse_cookie_date);
+        let lifetime = match cc.max_age
+        {
+            Some(max_age) => max_age,
+            //некорректный Expires означает что ответ уже устарел
+            None => date(EXPIRES).map(|expires| expires.saturating_sub(date(DATE).unwrap_or(self.stored))).unwrap_or_default()
+        };
+        let age = headers.get(AGE).and_then(|v| v.to_str().ok()).and_then(|v| v.trim().parse::<u64>().ok()).unwrap_or_default();
+        lifetime > age + now().saturating_sub(self.stored)
+    }
+    ///заголовки условного запроса, заданные явно не заменяются, false если добавить нечего
+    fn add_conditions(&self, headers: &mut HeaderMap) -> bool
+    {
+        let stored = self.header_map();
+        let mut added = false;
+        for (validator, condition) in [(ETAG, IF_NONE_MATCH), (LAST_MODIFIED, IF_MODIFIED_SINCE)]
+        {
+            if let Some(value) = stored.get(validator)
+            {
+                if !headers.contains_key(&condition)
+                {
+                    headers.insert(condition, value.clone());
+                    added = true;
+                }
+            }
+        }
+        added
+    }
+    ///заголовки ответа 304 заменяют сохраненные (RFC 9111 4.3.4)
+    fn revalidate(&mut self, headers: &HeaderMap)
+    {
+        let skip = [CONTENT_LENGTH, CONTENT_ENCODING, TRANSFER_ENCODING, SET_COOKIE];
+        let updated = headers.keys().filter(|name| !skip.contains(name)).map(|name| name.as_str()).collect::<Vec<_>>();
+        self.headers.retain(|(name, _)| !updated.contains(&name.as_str()));
+        for (name, value) in headers.iter().filter(|(name, _)| !skip.contains(name))
+        {
+            self.headers.push((name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()));
+        }
+        self.stored = now();
+    }
+    fn to_response(&self, elapsed: std::time::Duration) -> ClientResponse
+    {
+        let version = match self.version.as_str()
+        {
+            "HTTP/1.0" => Version::HTTP_10,
+            "HTTP/2.0" => Version::HTTP_2,
+            "HTTP/3.0" => Version::HTTP_3,
+            _ => Version::HTTP_11
+        };
+        ClientResponse
+        {
+            status: StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK),
+            version,
+            headers: self.header_map(),
+            url: self.url.parse().unwrap_or_default(),
+            redirects: self.redirects.iter().filter_map(|r| r.parse().ok()).collect(),
+            elapsed,
+            from_cache: true,
+            body: self.body.clone()
+        }
+    }
+}
+
+///все значения заголовка запроса через запятую
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String>
+{
+    let values = headers.get_all(name).iter().map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned()).collect::<Vec<_>>();
+    if values.is_empty() { None } else { Some(values.join(", ")) }
+}
+
+fn hash(value: &str) -> String
+{
+    ring::digest::digest(&ring::digest::SHA256, value.as_bytes()).as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+///запись через временный файл с уникальным именем: при сбое не остается файл записанный наполовину, одновременные записи не мешают друг другу
+async fn write_file(path: &Path, data: &[u8]) -> std::io::Result<()>
+{
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{:016x}.tmp", rand::random::<u64>()));
+    let tmp = PathBuf::from(tmp);
+    tokio::fs::write(&tmp, data).await?;
+    let result = tokio::fs::rename(&tmp, path).await;
+    if result.is_err()
+    {
+        let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    result
+}
+
+fn now() -> u64
+{
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{net::SocketAddr, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+    use hyper::{body::Bytes, header::{HeaderValue, ACCEPT_LANGUAGE, AUTHORIZATION, CACHE_CONTROL, ETAG, IF_NONE_MATCH, LAST_MODIFIED, VARY}, Method, Request, StatusCode};
+    use hyper_util::rt::TokioIo;
+    use super::HttpCache;
+    use crate::http::{to_body, HyperClient};
+
+    ///`/etag` - ETag "v1" и 304 на If-None-Match, `/fresh` и `/other` - max-age=60, `/vary` - Vary: Accept-Language,
+    ///`/no-store` - ETag с no-store; тело - номер запроса к серверу
+    async fn cache_server() -> (SocketAddr, Arc<AtomicUsize>)
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move
+        {
+            loop
+            {
+                let (stream, _) = listener.accept().await.unwrap();
+                let counter = counter.clone();
+                tokio::spawn(async move
+                {
+                    let service = hyper::service::service_fn(move |req: Request<hyper::body::Incoming>|
+                    {
+                        let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                        async move
+                        {
+                            if req.headers().get(IF_NONE_MATCH).is_some_and(|v| v == "\"v1\"")
+                            {
+                                let mut response = crate::http::empty_response(StatusCode::NOT_MODIFIED);
+                                response.headers_mut().insert(ETAG, HeaderValue::from_static("\"v1\""));
+                                response.headers_mut().insert("x-revalidated", HeaderValue::from_static("yes"));
+                                return Ok::<_, hyper::Error>(response);
+                            }
+                            let mut response = hyper::Response::new(to_body(Bytes::from(n.to_string())));
+                            let headers = response.headers_mut();
+                            match req.uri().path()
+                            {
+                                "/etag" =>
+                                {
+                                    headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+                                    headers.insert(LAST_MODIFIED, HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT"));
+                                },
+                                "/vary" =>
+                                {
+                                    headers.insert(CACHE_CONTROL, HeaderValue::from_static("max-age=60"));
+                                    headers.insert(VARY, HeaderValue::from_static("Accept-Language"));
+                                },
+                                "/no-store" =>
+                                {
+                                    headers.insert(ETAG, HeaderValue::from_static("\"v2\""));
+                                    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+                                },
+                                _ =>
+                                {
+                                    headers.insert(CACHE_CONTROL, HeaderValue::from_static("public, max-age=60"));
+                                }
+                            };
+                            Ok(response)
+                        }
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        (addr, requests)
+    }
+
+    fn client(addr: SocketAddr, path: &str, cache: &HttpCache) -> HyperClient
+    {
+        HyperClient::builder(format!("http://{}{}", addr, path)).with_retry_count(1).with_cache(cache.clone()).build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cache_memory()
+    {
+        let (addr, requests) = cache_server().await;
+        let cache = HttpCache::memory(2);
+        //устаревший ответ проверяется условным запросом, на 304 возвращается сохраненное тело
+        let etag = client(addr, "/etag", &cache);
+        let first = etag.get().await.unwrap();
+        assert!(!first.from_cache());
+        let second = etag.get().await.unwrap();
+        assert!(second.from_cache());
+        assert_eq!((second.status(), second.text()), (StatusCode::OK, "1".to_owned()));
+        assert_eq!(second.headers().get("x-revalidated").unwrap(), "yes");
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        //свежий ответ без запроса к серверу
+        let fresh = client(addr, "/fresh", &cache);
+        assert_eq!(fresh.get().await.unwrap().text(), "3");
+        let cached = fresh.get().await.unwrap();
+        assert!(cached.from_cache());
+        assert_eq!(cached.text(), "3");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        //Cache-Control: no-cache в запросе требует обращения к серверу
+        assert_eq!(fresh.clone().with_header(CACHE_CONTROL, "no-cache").get().await.unwrap().text(), "4");
+        //изменение ресурса удаляет сохраненный ответ
+        fresh.request(Method::POST, &[] as &[(&str, &str)], None::<()>).await.unwrap();
+        assert_eq!(fresh.get().await.unwrap().text(), "6");
+
+        //no-store не сохраняется
+        let no_store = client(addr, "/no-store", &cache);
+        no_store.get().await.unwrap();
+        assert!(!no_store.get().await.unwrap().from_cache());
+        assert_eq!(requests.load(Ordering::SeqCst), 8);
+
+        //в кэше не больше двух адресов: /etag запрашивался раньше всех и вытеснен
+        assert_eq!(client(addr, "/other", &cache).get().await.unwrap().text(), "9");
+        assert_eq!(fresh.get().await.unwrap().text(), "6");
+        assert!(!etag.get().await.unwrap().from_cache());
+        cache.clear().await.unwrap();
+        assert!(!fresh.get().await.unwrap().from_cache());
+    }
+
+    #[tokio::test]
+    async fn test_cache_authorization()
+    {
+        let (addr, requests) = cache_server().await;
+        let cache = HttpCache::memory(10);
+        //ответ на запрос с учетными данными без public не сохраняется
+        let etag = client(addr, "/etag", &cache).with_bearer_auth("secret");
+        assert_eq!(etag.get().await.unwrap().text(), "1");
+        assert_eq!(etag.get().await.unwrap().text(), "2");
+        let etag = client(addr, "/etag", &cache).with_header(AUTHORIZATION, "Basic dXNlcjpzZWNyZXQ=");
+        assert_eq!(etag.get().await.unwrap().text(), "3");
+        assert!(!client(addr, "/etag", &cache).get().await.unwrap().from_cache());
+        //Cache-Control: public разрешает сохранение
+        assert_eq!(client(addr, "/fresh", &cache).with_bearer_auth("secret").get().await.unwrap().text(), "5");
+        assert!(client(addr, "/fresh", &cache).get().await.unwrap().from_cache());
+        assert_eq!(requests.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_cache_vary()
+    {
+        let (addr, requests) = cache_server().await;
+        let cache = HttpCache::memory(10);
+        let vary = client(addr, "/vary", &cache);
+        let ru = vary.clone().with_header(ACCEPT_LANGUAGE, "ru");
+        let en = vary.clone().with_header(ACCEPT_LANGUAGE, "en");
+        assert_eq!(ru.get().await.unwrap().text(), "1");
+        assert_eq!(en.get().await.unwrap().text(), "2");
+        assert_eq!(vary.get().await.unwrap().text(), "3");
+        assert_eq!(ru.get().await.unwrap().text(), "1");
+        assert_eq!(en.get().await.unwrap().text(), "2");
+        assert_eq!(vary.get().await.unwrap().text(), "3");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_cache_disk()
+    {
+        let (addr, requests) = cache_server().await;
+        let dir = std::env::temp_dir().join(format!("utilites_cache_{}", addr.port()));
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(client(addr, "/fresh", &HttpCache::disk(&dir)).get().await.unwrap().text(), "1");
+        assert_eq!(client(addr, "/etag", &HttpCache::disk(&dir)).get().await.unwrap().text(), "2");
+        //сохраненные ответы доступны новому кэшу в том же каталоге
+        let cache = HttpCache::disk(&dir);
+        let cached = client(addr, "/fresh", &cache).get().await.unwrap();
+        assert!(cached.from_cache());
+        assert_eq!(cached.text(), "1");
+        let revalidated = client(addr, "/etag", &cache).get().await.unwrap();
+        assert!(revalidated.from_cache());
+        assert_eq!(revalidated.text(), "2");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        cache.clear().await.unwrap();
+        assert!(!dir.exists());
+        assert_eq!(client(addr, "/fresh", &cache).get().await.unwrap().text(), "4");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        //одновременная запись одного файла
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("entry.json");
+        let writes = (0..16u8).map(|i| { let path = path.clone(); async move { super::write_file(&path, &[i; 1024]).await } });
+        assert!(futures::future::join_all(writes).await.iter().all(|r| r.is_ok()));
+        let data = std::fs::read(&path).unwrap();
+        assert!(data.len() == 1024 && data.iter().all(|b| *b == data[0]));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,440 @@
+use std::{net::IpAddr, path::Path, sync::{Arc, Mutex}, time::{SystemTime, UNIX_EPOCH}};
+use hyper::{header::{HeaderMap, HeaderValue, SET_COOKIE}, Uri};
+use serde::{Deserialize, Serialize};
+use crate::{error::Error, Serializer};
+
+///Cookie сохраненный в `CookieJar`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Cookie
+{
+    pub name: String,
+    pub value: String,
+    ///домен в нижнем регистре, без ведущей точки
+    pub domain: String,
+    ///атрибут Domain не был указан, cookie отправляется только на хост который его установил
+    pub host_only: bool,
+    pub path: String,
+    ///время истечения в секундах unix, `None` - cookie сессии
+    pub expires: Option<u64>,
+    pub secure: bool,
+    pub http_only: bool
+}
+
+impl Cookie
+{
+    ///разбор значения заголовка Set-Cookie полученного в ответ на запрос `uri` (RFC 6265 5.2),
+    ///значение в кавычках сохраняется вместе с кавычками.
+    ///Domain из одной метки (`com`) или IP адрес принимается только равным хосту, cookie остается host-only,
+    ///список публичных суффиксов (`co.uk`) не проверяется
+    pub fn parse(set_cookie: &str, uri: &Uri) -> Option<Self>
+    {
+        let host = uri.host()?.trim_start_matches('[').trim_end_matches(']').to_lowercase();
+        let mut parts = set_cookie.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty()
+        {
+            return None;
+        }
+        let mut cookie = Cookie
+        {
+            name: name.to_owned(),
+            value: value.trim().to_owned(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(uri.path()),
+            expires: None,
+            secure: false,
+            http_only: false
+        };
+        let mut max_age: Option<i64> = None;
+        for attr in parts
+        {
+            let (key, value) = attr.split_once('=').unwrap_or((attr, ""));
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str()
+            {
+                "expires" =>
+                {
+                    if let Some(date) = parse_cookie_date(value)
+                    {
+                        cookie.expires = Some(date);
+                    }
+                },
+                "max-age" =>
+                {
+                    if let Ok(seconds) = value.parse::<i64>()
+                    {
+                        max_age = Some(seconds);
+                    }
+                },
+                "domain" =>
+                {
+                    let domain = value.trim_start_matches('.').to_lowercase();
+                    if domain.is_empty()
+                    {
+                        continue;
+                    }
+                    //иначе cookie отправлялся бы всем сайтам в зоне или хостам с таким окончанием адреса
+                    if !domain.contains('.') || domain.parse::<IpAddr>().is_ok()
+                    {
+                        if domain != host
+                        {
+                            return None;
+                        }
+                        cookie.host_only = true;
+                        continue;
+                    }
+                    //нельзя установить cookie для чужого домена
+                    if !domain_match(&host, &domain)
+                    {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                },
+                "path" if value.starts_with('/') => cookie.path = value.to_owned(),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                _ => ()
+            }
+        }
+        //Max-Age приоритетнее Expires
+        if let Some(seconds) = max_age
+        {
+            cookie.expires = Some(if seconds <= 0 { 0 } else { now().saturating_add(seconds as u64) });
+        }
+        Some(cookie)
+    }
+    pub fn is_expired(&self) -> bool
+    {
+        self.expires.is_some_and(|e| e <= now())
+    }
+    ///cookie должен быть отправлен в запросе на `uri`
+    pub fn matches(&self, uri: &Uri) -> bool
+    {
+        let host = match uri.host()
+        {
+            Some(h) => h.trim_start_matches('[').trim_end_matches(']').to_lowercase(),
+            None => return false
+        };
+        let domain_ok = if self.host_only
+        {
+            host == self.domain
+        }
+        else
+        {
+            domain_match(&host, &self.domain)
+        };
+        domain_ok
+        && path_match(uri.path(), &self.path)
+        && (!self.secure || uri.scheme_str() == Some("https"))
+        && !self.is_expired()
+    }
+}
+
+///Хранилище cookie одного клиента, клоны используют одно и то же хранилище
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar
+{
+    cookies: Arc<Mutex<Vec<Cookie>>>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct StoredCookies
+{
+    cookies: Vec<Cookie>
+}
+
+impl CookieJar
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+    ///Сохранение cookie из всех заголовков Set-Cookie ответа на запрос `uri`
+    ///возвращает true если содержимое хранилища изменилось
+    pub fn store_response_cookies(&self, uri: &Uri, headers: &HeaderMap) -> bool
+    {
+        let mut changed = false;
+        for header in headers.get_all(SET_COOKIE)
+        {
+            if let Ok(set_cookie) = header.to_str()
+            {
+                changed |= self.insert(uri, set_cookie);
+            }
+        }
+        changed
+    }
+    ///Добавление cookie из значения заголовка Set-Cookie, истекший cookie удаляет ранее сохраненный
+    ///возвращает true если содержимое хранилища изменилось
+    pub fn insert(&self, uri: &Uri, set_cookie: &str) -> bool
+    {
+        let cookie = match Cookie::parse(set_cookie, uri)
+        {
+            Some(c) => c,
+            None =>
+            {
+                tracing::warn!("Некорректный заголовок Set-Cookie `{}` от {}", set_cookie, uri);
+                return false;
+            }
+        };
+        let mut guard = self.cookies.lock().unwrap();
+        let old = guard.iter().position(|c| c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path);
+        match (old, cookie.is_expired())
+        {
+            (Some(i), true) =>
+            {
+                guard.remove(i);
+                true
+            },
+            (None, true) => false,
+            (Some(i), false) =>
+            {
+                let changed = guard[i] != cookie;
+                guard[i] = cookie;
+                changed
+            },
+            (None, false) =>
+            {
+                guard.push(cookie);
+                true
+            }
+        }
+    }
+    ///Значение заголовка Cookie для запроса на `uri`, cookie с более длинным path идут первыми
+    pub fn cookie_header(&self, uri: &Uri) -> Option<HeaderValue>
+    {
+        let mut guard = self.cookies.lock().unwrap();
+        guard.retain(|c| !c.is_expired());
+        let mut matched: Vec<&Cookie> = guard.iter().filter(|c| c.matches(uri)).collect();
+        if matched.is_empty()
+        {
+            return None;
+        }
+        matched.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+        let header = matched
+            .iter()
+            .map(|c| [c.name.as_str(), "=", c.value.as_str()].concat())
+            .collect::<Vec<String>>()
+            .join("; ");
+        HeaderValue::from_str(&header).ok()
+    }
+    ///Копия всех непросроченных cookie
+    pub fn cookies(&self) -> Vec<Cookie>
+    {
+        self.cookies.lock().unwrap().iter().filter(|c| !c.is_expired()).cloned().collect()
+    }
+    pub fn clear(&self)
+    {
+        self.cookies.lock().unwrap().clear();
+    }
+    ///Сохранение cookie в файл, включая cookie сессии
+    pub fn save<P: AsRef<Path>>(&self, file_path: P, serializer: Serializer) -> Result<(), Error>
+    {
+        let stored = StoredCookies { cookies: self.cookies() };
+        crate::serialize(stored, file_path, true, serializer)
+    }
+    ///Загрузка cookie из файла сохраненного через `save`, просроченные cookie отбрасываются
+    pub fn load<P: AsRef<Path>>(file_path: P, serializer: Serializer) -> Result<Self, Error>
+    {
+        let stored: StoredCookies = crate::deserialize(file_path, true, serializer)?;
+        let cookies = stored.cookies.into_iter().filter(|c| !c.is_expired()).collect();
+        Ok(Self { cookies: Arc::new(Mutex::new(cookies)) })
+    }
+}
+
+fn now() -> u64
+{
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+///path по умолчанию - путь запроса до последнего `/` (RFC 6265 5.1.4)
+fn default_path(path: &str) -> String
+{
+    match path.rfind('/')
+    {
+        Some(0) | None => "/".to_owned(),
+        Some(i) => path[..i].to_owned()
+    }
+}
+
+fn domain_match(host: &str, domain: &str) -> bool
+{
+    if host == domain
+    {
+        return true;
+    }
+    host.parse::<IpAddr>().is_err()
+    && host.len() > domain.len()
+    && host.ends_with(domain)
+    && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+}
+
+fn path_match(request_path: &str, cookie_path: &str) -> bool
+{
+    let request_path = if request_path.is_empty() { "/" } else { request_path };
+    request_path == cookie_path
+    || (request_path.starts_with(cookie_path)
+        && (cookie_path.ends_with('/') || request_path.as_bytes()[cookie_path.len()] == b'/'))
+}
+
+///Разбор даты атрибута Expires по алгоритму RFC 6265 5.1.1
+///поддерживает `Sun, 06 Nov 1994 08:49:37 GMT`, `Sunday, 06-Nov-94 08:49:37 GMT` и `Sun Nov  6 08:49:37 1994`
+pub(super) fn parse_cookie_date(value: &str) -> Option<u64>
+{
+    const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+    let mut time: Option<(u64, u64, u64)> = None;
+    let mut day: Option<u64> = None;
+    let mut month: Option<u64> = None;
+    let mut year: Option<i64> = None;
+    let tokens = value
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == ':'))
+        .filter(|t| !t.is_empty());
+    for token in tokens
+    {
+        if time.is_none() && token.contains(':')
+        {
+            let parts: Vec<u64> = token.split(':').filter_map(|p| p.parse().ok()).collect();
+            if parts.len() == 3
+            {
+                time = Some((parts[0], parts[1], parts[2]));
+                continue;
+            }
+        }
+        let digits = token.chars().take_while(|c| c.is_ascii_digit()).count();
+        if day.is_none() && (1..=2).contains(&digits)
+        {
+            day = token[..digits].parse().ok();
+            continue;
+        }
+        if month.is_none() && token.len() >= 3
+        {
+            if let Some(m) = MONTHS.iter().position(|m| token[..3].eq_ignore_ascii_case(m))
+            {
+                month = Some(m as u64 + 1);
+                continue;
+            }
+        }
+        if year.is_none() && (2..=4).contains(&digits)
+        {
+            year = token[..digits].parse().ok();
+        }
+    }
+    let (hour, minute, second) = time?;
+    let (day, month) = (day?, month?);
+    let year = match year?
+    {
+        y @ 70..=99 => y + 1900,
+        y @ 0..=69 => y + 2000,
+        y => y
+    };
+    if !(1..=31).contains(&day) || year < 1601 || hour > 23 || minute > 59 || second > 59
+    {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    if days < 0
+    {
+        return Some(0);
+    }
+    Some(days as u64 * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+///количество дней от 1970-01-01 до указанной даты
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64
+{
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests
+{
+    use hyper::{header::{HeaderMap, HeaderValue, SET_COOKIE}, Uri};
+    use super::{parse_cookie_date, CookieJar};
+
+    #[test]
+    fn test_parse_cookie_date()
+    {
+        assert_eq!(parse_cookie_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784111777));
+        assert_eq!(parse_cookie_date("Sunday, 06-Nov-94 08:49:37 GMT"), Some(784111777));
+        assert_eq!(parse_cookie_date("Sun Nov  6 08:49:37 1994"), Some(784111777));
+        assert_eq!(parse_cookie_date("Wed, 21 Oct 2015 07:28:00 GMT"), Some(1445412480));
+        assert_eq!(parse_cookie_date("not a date"), None);
+    }
+
+    #[test]
+    fn test_cookie_jar_domain_and_path()
+    {
+        let jar = CookieJar::new();
+        let uri: Uri = "https://www.example.com/docs/index.html".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, HeaderValue::from_static("host=1"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("wide=2; Domain=.example.com; Path=/"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("secure=3; Secure; HttpOnly"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("foreign=4; Domain=other.com"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("zone=5; Domain=com"));
+        assert!(jar.store_response_cookies(&uri, &headers));
+        assert_eq!(jar.cookies().len(), 3);
+        assert!(jar.cookie_header(&"https://shop.com/".parse().unwrap()).is_none());
+
+        let same: Uri = "https://www.example.com/docs/page".parse().unwrap();
+        assert_eq!(jar.cookie_header(&same).unwrap(), "host=1; secure=3; wide=2");
+        let plain: Uri = "http://www.example.com/docs/page".parse().unwrap();
+        assert_eq!(jar.cookie_header(&plain).unwrap(), "host=1; wide=2");
+        let sub: Uri = "https://api.example.com/docs".parse().unwrap();
+        assert_eq!(jar.cookie_header(&sub).unwrap(), "wide=2");
+        let other_path: Uri = "https://www.example.com/documents".parse().unwrap();
+        assert_eq!(jar.cookie_header(&other_path).unwrap(), "wide=2");
+        let other: Uri = "https://example.org/".parse().unwrap();
+        assert!(jar.cookie_header(&other).is_none());
+
+        //домен из одной метки и IP адрес допустимы только для самого хоста
+        let jar = CookieJar::new();
+        let local: Uri = "http://localhost/".parse().unwrap();
+        assert!(jar.insert(&local, "a=1; Domain=localhost"));
+        assert!(jar.cookies()[0].host_only);
+        let ip: Uri = "http://10.0.0.1/".parse().unwrap();
+        assert!(jar.insert(&ip, "b=2; Domain=10.0.0.1"));
+        assert!(!jar.insert(&ip, "c=3; Domain=0.0.1"));
+        assert!(!jar.insert(&"http://www.example.com/".parse().unwrap(), "d=4; Domain=10.0.0.1"));
+        assert_eq!(jar.cookie_header(&ip).unwrap(), "b=2");
+        //значение в кавычках сохраняется как есть
+        assert!(jar.insert(&local, "q=\"x y\""));
+        assert_eq!(jar.cookie_header(&local).unwrap(), "a=1; q=\"x y\"");
+    }
+
+    #[test]
+    fn test_cookie_jar_expiration()
+    {
+        let jar = CookieJar::new();
+        let uri: Uri = "http://example.com/".parse().unwrap();
+        assert!(jar.insert(&uri, "a=1; Max-Age=3600"));
+        assert!(!jar.insert(&uri, "b=2; Expires=Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert!(jar.insert(&uri, "c=3; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Max-Age=60"));
+        assert_eq!(jar.cookie_header(&uri).unwrap(), "a=1; c=3");
+        assert!(jar.insert(&uri, "a=deleted; Max-Age=0"));
+        assert_eq!(jar.cookie_header(&uri).unwrap(), "c=3");
+    }
+
+    #[test]
+    fn test_cookie_jar_save_load()
+    {
+        let jar = CookieJar::new();
+        let uri: Uri = "https://example.com/api/v1".parse().unwrap();
+        jar.insert(&uri, "session=abc; Secure");
+        jar.insert(&uri, "lang=ru; Path=/; Max-Age=3600");
+        let file = std::env::temp_dir().join("utilites_test_cookie_jar.json");
+        jar.save(&file, crate::Serializer::Json).unwrap();
+        let loaded = CookieJar::load(&file, crate::Serializer::Json).unwrap();
+        let _ = std::fs::remove_file(&file);
+        assert_eq!(loaded.cookies(), jar.cookies());
+        assert_eq!(loaded.cookie_header(&uri).unwrap(), "session=abc; lang=ru");
+    }
+}
@@ -0,0 +1,40 @@
+use async_compression::tokio::bufread::{BrotliDecoder, GzipDecoder, ZlibDecoder};
+use futures::TryStreamExt;
+use hyper::{header::{CONTENT_ENCODING, CONTENT_LENGTH}, HeaderMap, StatusCode};
+use tokio_util::io::{ReaderStream, StreamReader};
+use super::BodyStream;
+use crate::error::Error;
+
+///Значение Accept-Encoding для поддерживаемых алгоритмов сжатия
+pub(crate) const ACCEPT_ENCODING_VALUE: &str = "gzip, deflate, br";
+
+///Распаковка тела ответа по заголовку Content-Encoding
+///для сжатого тела заголовки Content-Encoding и Content-Length удаляются, так как больше не соответствуют телу
+pub(crate) fn decompress(status: StatusCode, headers: &mut HeaderMap, body: BodyStream) -> BodyStream
+{
+    let encoding = match headers.get(CONTENT_ENCODING).and_then(|e| e.to_str().ok())
+    {
+        Some(e) => e.trim().to_ascii_lowercase(),
+        None => return body
+    };
+    let empty = headers.get(CONTENT_LENGTH).is_some_and(|l| l == "0");
+    if empty || status == StatusCode::NO_CONTENT || status == StatusCode::NOT_MODIFIED
+    {
+        return body;
+    }
+    if !matches!(encoding.as_str(), "gzip" | "x-gzip" | "deflate" | "br")
+    {
+        tracing::debug!("Тело ответа сжато неподдерживаемым алгоритмом `{}` и возвращается как есть", encoding);
+        return body;
+    }
+    let reader = StreamReader::new(body.map_err(std::io::Error::other));
+    let decoded: BodyStream = match encoding.as_str()
+    {
+        "deflate" => Box::pin(ReaderStream::new(ZlibDecoder::new(reader)).map_err(Error::from)),
+        "br" => Box::pin(ReaderStream::new(BrotliDecoder::new(reader)).map_err(Error::from)),
+        _ => Box::pin(ReaderStream::new(GzipDecoder::new(reader)).map_err(Error::from))
+    };
+    headers.remove(CONTENT_ENCODING);
+    headers.remove(CONTENT_LENGTH);
+    decoded
+}
@@ -0,0 +1,326 @@
+//!Извлечение типизированных данных из запроса для обработчиков `Router`.
+//!При ошибке возвращается готовый ответ 400 (413 если тело больше ограничения) с описанием ошибки,
+//!поэтому в обработчике, возвращающем `Result<Response<BoxBody>, Response<BoxBody>>`, достаточно `?`
+//!```ignore
+//!router.put("/users/:id/items", |req, db: Db| async move
+//!{
+//!    let id: u64 = extract::path_param(&req, "id")?;
+//!    let page: Page = extract::query(&req)?;
+//!    let token: String = extract::header(&req, AUTHORIZATION)?;
+//!    let item: Item = extract::json(req, 64 * 1024).await?;
+//!    Ok::<_, Response<BoxBody>>(json_response(&db.put(id, page, item).await))
+//!})
+//!```
+//ошибка - готовый ответ для обработчика, размер Result не важен
+#![allow(clippy::result_large_err)]
+use std::{fmt::Display, str::FromStr};
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+use hyper::{body::{Body, Bytes}, header::{AsHeaderName, CONTENT_LENGTH, CONTENT_TYPE}, Request, Response, StatusCode};
+use serde::{de::{self, value::{Error as DeError, MapDeserializer}, DeserializeOwned, IntoDeserializer, Visitor}, forward_to_deserialize_any, Deserializer};
+use super::{error_response, BoxBody, PathParams};
+
+fn bad_request(msg: String) -> Response<BoxBody>
+{
+    error_response(msg, StatusCode::BAD_REQUEST)
+}
+
+///Параметры адреса запроса в структуру `T`, значения разбираются по типам полей,
+///повторяющиеся параметры не поддерживаются
+pub fn query<T: DeserializeOwned, B>(req: &Request<B>) -> Result<T, Response<BoxBody>>
+{
+    let pairs = url::form_urlencoded::parse(req.uri().query().unwrap_or_default().as_bytes()).into_owned();
+    from_pairs(pairs).map_err(|e| bad_request(format!("Некорректные параметры запроса: {}", e)))
+}
+
+///Параметры пути маршрута `:name` в структуру `T`
+pub fn path<T: DeserializeOwned, B>(req: &Request<B>) -> Result<T, Response<BoxBody>>
+{
+    let params = req.extensions().get::<PathParams>().cloned().unwrap_or_default();
+    from_pairs(params.iter().map(|(k, v)| (k.to_owned(), v.to_owned()))).map_err(|e| bad_request(format!("Некорректные параметры пути: {}", e)))
+}
+
+///Параметр пути маршрута `:name`
+pub fn path_param<T: FromStr, B>(req: &Request<B>, name: &str) -> Result<T, Response<BoxBody>>
+where T::Err: Display
+{
+    let value = req
+        .extensions()
+        .get::<PathParams>()
+        .and_then(|p| p.get(name))
+        .ok_or_else(|| bad_request(format!("Отсутствует параметр пути `{}`", name)))?;
+    value.parse().map_err(|e| bad_request(format!("Некорректный параметр пути `{}` = `{}`: {}", name, value, e)))
+}
+
+///Обязательный заголовок запроса
+pub fn header<T: FromStr, B, N: AsHeaderName + Display + Clone>(req: &Request<B>, name: N) -> Result<T, Response<BoxBody>>
+where T::Err: Display
+{
+    optional_header(req, name.clone())?.ok_or_else(|| bad_request(format!("Отсутствует заголовок `{}`", name)))
+}
+
+///Заголовок запроса, если он есть
+pub fn optional_header<T: FromStr, B, N: AsHeaderName + Display + Clone>(req: &Request<B>, name: N) -> Result<Option<T>, Response<BoxBody>>
+where T::Err: Display
+{
+    let Some(value) = req.headers().get(name.clone()) else { return Ok(None) };
+    let value = value.to_str().map_err(|_| bad_request(format!("Заголовок `{}` содержит недопустимые символы", name)))?;
+    value.parse().map(Some).map_err(|e| bad_request(format!("Некорректный заголовок `{}` = `{}`: {}", name, value, e)))
+}
+
+///Тело запроса не больше `limit` байт
+pub async fn bytes<B>(req: Request<B>, limit: usize) -> Result<Bytes, Response<BoxBody>>
+where B: Body,
+      B::Error: Into<Box<dyn std::error::Error + Send + Sync>>
+{
+    let too_large = || error_response(format!("Размер тела запроса превышает {} байт", limit), StatusCode::PAYLOAD_TOO_LARGE);
+    let length = req.headers().get(CONTENT_LENGTH).and_then(|l| l.to_str().ok()?.parse::<usize>().ok());
+    if length.is_some_and(|l| l > limit)
+    {
+        return Err(too_large());
+    }
+    match Limited::new(req.into_body(), limit).collect().await
+    {
+        Ok(body) => Ok(body.to_bytes()),
+        Err(e) if e.is::<LengthLimitError>() => Err(too_large()),
+        Err(e) => Err(bad_request(format!("Ошибка чтения тела запроса: {}", e)))
+    }
+}
+
+///Тело JSON не больше `limit` байт, Content-Type если указан должен быть `application/json` или `*+json`
+pub async fn json<T: DeserializeOwned, B>(req: Request<B>, limit: usize) -> Result<T, Response<BoxBody>>
+where B: Body,
+      B::Error: Into<Box<dyn std::error::Error + Send + Sync>>
+{
+    check_content_type(&req, |mime| mime == "application/json" || mime.ends_with("+json"), "application/json")?;
+    let body = bytes(req, limit).await?;
+    serde_json::from_slice(&body).map_err(|e| bad_request(format!("Некорректное тело запроса JSON: {}", e)))
+}
+
+///Тело `application/x-www-form-urlencoded` не больше `limit` байт в структуру `T`
+pub async fn form<T: DeserializeOwned, B>(req: Request<B>, limit: usize) -> Result<T, Response<BoxBody>>
+where B: Body,
+      B::Error: Into<Box<dyn std::error::Error + Send + Sync>>
+{
+    check_content_type(&req, |mime| mime == "application/x-www-form-urlencoded", "application/x-www-form-urlencoded")?;
+    let body = bytes(req, limit).await?;
+    from_pairs(url::form_urlencoded::parse(&body).into_owned()).map_err(|e| bad_request(format!("Некорректные данные формы: {}", e)))
+}
+
+fn check_content_type<B, F: Fn(&str) -> bool>(req: &Request<B>, accepted: F, expected: &str) -> Result<(), Response<BoxBody>>
+{
+    let Some(content_type) = req.headers().get(CONTENT_TYPE) else { return Ok(()) };
+    let mime = content_type.to_str().unwrap_or_default().split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    if accepted(&mime)
+    {
+        Ok(())
+    }
+    else
+    {
+        Err(bad_request(format!("Некорректный Content-Type `{}`, ожидается {}", mime, expected)))
+    }
+}
+
+fn from_pairs<T: DeserializeOwned, I: Iterator<Item = (String, String)>>(pairs: I) -> Result<T, DeError>
+{
+    T::deserialize(MapDeserializer::new(pairs.map(|(k, v)| (k, Value(v)))))
+}
+
+///Строковое значение параметра, разбирается в тип поля при десериализации
+struct Value(String);
+
+impl IntoDeserializer<'_, DeError> for Value
+{
+    type Deserializer = Self;
+    fn into_deserializer(self) -> Self
+    {
+        self
+    }
+}
+
+macro_rules! parse_value
+{
+    ($($method:ident => $visit:ident),*) =>
+    {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError>
+            {
+                match self.0.parse()
+                {
+                    Ok(v) => visitor.$visit(v),
+                    Err(e) => Err(de::Error::custom(format!("`{}` {}", self.0, e)))
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Value
+{
+    type Error = DeError;
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError>
+    {
+        visitor.visit_string(self.0)
+    }
+    parse_value!
+    (
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64
+    );
+    ///параметр присутствует - Some
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError>
+    {
+        visitor.visit_some(self)
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, DeError>
+    {
+        visitor.visit_newtype_struct(self)
+    }
+    ///только варианты без данных
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, DeError>
+    {
+        visitor.visit_enum(IntoDeserializer::<DeError>::into_deserializer(self.0))
+    }
+    forward_to_deserialize_any!
+    {
+        char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::collections::HashMap;
+    use http_body_util::{BodyExt, Full};
+    use hyper::{body::Bytes, header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE}, Request, Response, StatusCode};
+    use serde::{Deserialize, Serialize};
+    use crate::http::{json_response, BoxBody, HttpServer, HyperClient, PathParams, Router};
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Sort
+    {
+        Asc,
+        Desc
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Page
+    {
+        page: u32,
+        size: Option<u8>,
+        filter: Option<String>,
+        sort: Sort,
+        archived: bool
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Item
+    {
+        id: u64,
+        name: String
+    }
+
+    async fn error_text(response: Response<BoxBody>) -> (StatusCode, String)
+    {
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    fn request(uri: &str, content_type: Option<&str>, body: &str) -> Request<Full<Bytes>>
+    {
+        let mut builder = Request::builder().uri(uri);
+        if let Some(content_type) = content_type
+        {
+            builder = builder.header(CONTENT_TYPE, content_type);
+        }
+        builder.body(Full::new(Bytes::from(body.to_owned()))).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_extract_query_path_header()
+    {
+        let mut req = request("/items?page=2&filter=%D1%81%D1%82%D0%BE%D0%BB&sort=desc&archived=true", None, "");
+        req.headers_mut().insert(AUTHORIZATION, "42".parse().unwrap());
+        let page: Page = super::query(&req).unwrap();
+        assert_eq!(page, Page { page: 2, size: None, filter: Some("стол".to_owned()), sort: Sort::Desc, archived: true });
+        let map: HashMap<String, String> = super::query(&req).unwrap();
+        assert_eq!(map["sort"], "desc");
+        assert_eq!(super::header::<u32, _, _>(&req, AUTHORIZATION).unwrap(), 42);
+        assert_eq!(super::optional_header::<String, _, _>(&req, "x-missing").unwrap(), None);
+        let (status, text) = error_text(super::header::<u32, _, _>(&req, "x-missing").unwrap_err()).await;
+        assert_eq!((status, text.as_str()), (StatusCode::BAD_REQUEST, "Отсутствует заголовок `x-missing`"));
+
+        let (status, text) = error_text(super::query::<Page, _>(&request("/items?page=x&sort=asc&archived=false", None, "")).unwrap_err()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(text.starts_with("Некорректные параметры запроса: `x`"), "{}", text);
+        let text = error_text(super::query::<Page, _>(&request("/items?page=1&archived=false", None, "")).unwrap_err()).await.1;
+        assert!(text.contains("sort"), "{}", text);
+        let text = error_text(super::query::<Page, _>(&request("/items?page=1&sort=up&archived=false", None, "")).unwrap_err()).await.1;
+        assert!(text.contains("up"), "{}", text);
+
+        let params = [("id", "7"), ("name", "стол")].into_iter().map(|(k, v)| (k.to_owned(), v.to_owned())).collect::<PathParams>();
+        req.extensions_mut().insert(params);
+        assert_eq!(super::path::<Item, _>(&req).unwrap(), Item { id: 7, name: "стол".to_owned() });
+        assert_eq!(super::path_param::<u64, _>(&req, "id").unwrap(), 7);
+        let text = error_text(super::path_param::<u64, _>(&req, "name").unwrap_err()).await.1;
+        assert!(text.starts_with("Некорректный параметр пути `name` = `стол`"), "{}", text);
+        assert!(super::path_param::<u64, _>(&req, "other").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_extract_body()
+    {
+        let item = Item { id: 1, name: "стол".to_owned() };
+        let body = serde_json::to_string(&item).unwrap();
+        assert_eq!(super::json::<Item, _>(request("/", Some("application/json; charset=utf-8"), &body), 1024).await.unwrap(), item);
+        assert_eq!(super::json::<Item, _>(request("/", None, &body), 1024).await.unwrap(), item);
+        let (status, text) = error_text(super::json::<Item, _>(request("/", Some("text/plain"), &body), 1024).await.unwrap_err()).await;
+        assert_eq!((status, text.as_str()), (StatusCode::BAD_REQUEST, "Некорректный Content-Type `text/plain`, ожидается application/json"));
+        let (status, text) = error_text(super::json::<Item, _>(request("/", Some("application/json"), r#"{"id": 1}"#), 1024).await.unwrap_err()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(text.contains("name"), "{}", text);
+        //ограничение размера проверяется по Content-Length и при чтении тела
+        let status = super::json::<Item, _>(request("/", None, &body), 10).await.unwrap_err().status();
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        let mut req = request("/", None, &body);
+        req.headers_mut().insert(CONTENT_LENGTH, "1000".parse().unwrap());
+        assert_eq!(super::bytes(req, 100).await.unwrap_err().status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let form = request("/", Some("application/x-www-form-urlencoded"), "id=2&name=%D1%81%D1%82%D1%83%D0%BB");
+        assert_eq!(super::form::<Item, _>(form, 1024).await.unwrap(), Item { id: 2, name: "стул".to_owned() });
+        let text = error_text(super::form::<Item, _>(request("/", Some("application/json"), "id=2"), 1024).await.unwrap_err()).await.1;
+        assert!(text.starts_with("Некорректный Content-Type"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_extract_in_handler()
+    {
+        let router = Router::new().put("/items/:id", |req, _| async move
+        {
+            let id: u64 = super::path_param(&req, "id")?;
+            let mut item: Item = super::json(req, 1024).await?;
+            item.id = id;
+            Ok::<_, Response<BoxBody>>(json_response(&item))
+        });
+        let server = HttpServer::new(router, ()).bind("127.0.0.1:0").await.unwrap();
+        let client = |path: &str| HyperClient::builder(format!("http://{}{}", server.addr(), path)).with_retry_count(1).build().unwrap();
+        let item: Item = client("/items/5").put_json(&Item { id: 0, name: "стол".to_owned() }).await.unwrap();
+        assert_eq!(item, Item { id: 5, name: "стол".to_owned() });
+        let response = client("/items/x").put_with_body(&item).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.text().starts_with("Некорректный параметр пути `id`"));
+        server.shutdown().await;
+    }
+}
@@ -0,0 +1,302 @@
+use std::{fmt::Debug, sync::Arc, time::{Duration, Instant}};
+use hyper::{header::{HeaderName, HeaderValue}, StatusCode};
+use rand::Rng;
+use crate::error::Error;
+pub use hyper::http::{request::Parts as RequestParts, response::Parts as ResponseParts};
+
+///Обработчик попыток запроса `HyperClient`: логирование, метрики, добавление заголовков, изменение ответа.
+///Для каждой попытки (в том числе повтора) вызывается `before_request`, затем после получения заголовков ответа
+///(после всех перенаправлений) `after_response` или `on_error` если ответ не получен.
+///`before_request` вызывается в порядке добавления обработчиков, `after_response` и `on_error` - в обратном.
+///Данные между вызовами можно передать через `RequestParts::extensions`
+pub trait Middleware: Send + Sync
+{
+    ///ошибка прерывает попытку запроса
+    fn before_request(&self, _request: &mut RequestParts) -> Result<(), Error>
+    {
+        Ok(())
+    }
+    ///ошибка возвращается вместо ответа
+    fn after_response(&self, _request: &RequestParts, _response: &mut ResponseParts) -> Result<(), Error>
+    {
+        Ok(())
+    }
+    fn on_error(&self, _request: &RequestParts, _error: &Error)
+    {
+    }
+}
+
+///Цепочка обработчиков клиента, общая для всех его клонов
+#[derive(Clone, Default)]
+pub(crate) struct MiddlewareStack(Vec<Arc<dyn Middleware>>);
+
+impl Debug for MiddlewareStack
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        f.debug_tuple("MiddlewareStack").field(&self.0.len()).finish()
+    }
+}
+
+impl MiddlewareStack
+{
+    pub(crate) fn push(&mut self, middleware: Arc<dyn Middleware>)
+    {
+        self.0.push(middleware);
+    }
+    pub(crate) fn is_empty(&self) -> bool
+    {
+        self.0.is_empty()
+    }
+    ///при ошибке `on_error` вызывается у всех обработчиков
+    pub(crate) fn before_request(&self, request: &mut RequestParts) -> Result<(), Error>
+    {
+        let result = self.0.iter().try_for_each(|m| m.before_request(request));
+        if let Err(e) = &result
+        {
+            self.on_error(request, e);
+        }
+        result
+    }
+    pub(crate) fn after_response(&self, request: &RequestParts, response: &mut ResponseParts) -> Result<(), Error>
+    {
+        self.0.iter().rev().try_for_each(|m| m.after_response(request, response))
+    }
+    pub(crate) fn on_error(&self, request: &RequestParts, error: &Error)
+    {
+        self.0.iter().rev().for_each(|m| m.on_error(request, error));
+    }
+}
+
+///Идентификатор запроса установленный `RequestIdMiddleware`, доступен в `RequestParts::extensions`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+///Добавляет к запросу заголовок `x-request-id` со случайным идентификатором, если он не задан,
+///каждая попытка запроса получает свой идентификатор
+#[derive(Debug, Clone)]
+pub struct RequestIdMiddleware
+{
+    header: HeaderName
+}
+
+impl Default for RequestIdMiddleware
+{
+    fn default() -> Self
+    {
+        Self { header: HeaderName::from_static("x-request-id") }
+    }
+}
+
+impl RequestIdMiddleware
+{
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+    pub fn with_header(header: HeaderName) -> Self
+    {
+        Self { header }
+    }
+}
+
+impl Middleware for RequestIdMiddleware
+{
+    fn before_request(&self, request: &mut RequestParts) -> Result<(), Error>
+    {
+        let id = match request.headers.get(&self.header).and_then(|h| h.to_str().ok())
+        {
+            Some(id) => id.to_owned(),
+            None =>
+            {
+                let mut rng = rand::rng();
+                let id = format!("{:016x}{:016x}", rng.random::<u64>(), rng.random::<u64>());
+                request.headers.insert(self.header.clone(), HeaderValue::from_str(&id).map_err(hyper::http::Error::from)?);
+                id
+            }
+        };
+        request.extensions.insert(RequestId(id));
+        Ok(())
+    }
+}
+
+///время начала попытки запроса
+#[derive(Clone, Copy)]
+struct AttemptStarted(Instant);
+
+fn started(request: &mut RequestParts)
+{
+    if request.extensions.get::<AttemptStarted>().is_none()
+    {
+        request.extensions.insert(AttemptStarted(Instant::now()));
+    }
+}
+
+fn elapsed(request: &RequestParts) -> Duration
+{
+    request.extensions.get::<AttemptStarted>().map(|s| s.0.elapsed()).unwrap_or_default()
+}
+
+///Span `http_request` с методом, адресом и идентификатором запроса (если до него добавлен `RequestIdMiddleware`),
+///внутри которого выполняется попытка запроса, и события отправки и завершения запроса с длительностью
+#[derive(Debug, Clone, Default)]
+pub struct TracingMiddleware;
+
+impl Middleware for TracingMiddleware
+{
+    fn before_request(&self, request: &mut RequestParts) -> Result<(), Error>
+    {
+        let request_id = request.extensions.get::<RequestId>().map(|r| r.0.clone()).unwrap_or_default();
+        let span = tracing::info_span!("http_request", method = %request.method, uri = %request.uri, request_id = %request_id);
+        span.in_scope(|| tracing::debug!("Отправка запроса"));
+        //клиент выполняет попытку внутри span из extensions
+        request.extensions.insert(span);
+        started(request);
+        Ok(())
+    }
+    fn after_response(&self, request: &RequestParts, response: &mut ResponseParts) -> Result<(), Error>
+    {
+        let span = request.extensions.get::<tracing::Span>().cloned().unwrap_or_else(tracing::Span::none);
+        span.in_scope(|| tracing::info!(status = response.status.as_u16(), elapsed = ?elapsed(request), "Получен ответ"));
+        Ok(())
+    }
+    fn on_error(&self, request: &RequestParts, error: &Error)
+    {
+        let span = request.extensions.get::<tracing::Span>().cloned().unwrap_or_else(tracing::Span::none);
+        span.in_scope(|| tracing::warn!(elapsed = ?elapsed(request), "Ошибка запроса: {}", error));
+    }
+}
+
+type TimingCallback = dyn Fn(&RequestParts, Option<StatusCode>, Duration) + Send + Sync;
+
+///Замер длительности попыток запроса для метрик: `callback` получает запрос,
+///статус ответа (None если ответ не получен) и время до получения заголовков ответа
+#[derive(Clone)]
+pub struct TimingMiddleware
+{
+    callback: Arc<TimingCallback>
+}
+
+impl TimingMiddleware
+{
+    pub fn new<F: Fn(&RequestParts, Option<StatusCode>, Duration) + Send + Sync + 'static>(callback: F) -> Self
+    {
+        Self { callback: Arc::new(callback) }
+    }
+}
+
+impl Middleware for TimingMiddleware
+{
+    fn before_request(&self, request: &mut RequestParts) -> Result<(), Error>
+    {
+        started(request);
+        Ok(())
+    }
+    fn after_response(&self, request: &RequestParts, response: &mut ResponseParts) -> Result<(), Error>
+    {
+        (self.callback)(request, Some(response.status), elapsed(request));
+        Ok(())
+    }
+    fn on_error(&self, request: &RequestParts, _error: &Error)
+    {
+        (self.callback)(request, None, elapsed(request));
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{net::SocketAddr, sync::{Arc, Mutex}, time::Duration};
+    use hyper::{body::Bytes, header::HeaderValue, Request, StatusCode};
+    use hyper_util::rt::TokioIo;
+    use super::{Middleware, RequestId, RequestIdMiddleware, RequestParts, ResponseParts, TimingMiddleware, TracingMiddleware};
+    use crate::{error::Error, http::{to_body, HyperClient}};
+
+    ///отвечает значением заголовка x-request-id, `/slow` через 500мс
+    async fn server() -> SocketAddr
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move
+        {
+            loop
+            {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move
+                {
+                    let service = hyper::service::service_fn(|req: Request<hyper::body::Incoming>| async move
+                    {
+                        if req.uri().path() == "/slow"
+                        {
+                            tokio::time::sleep(Duration::from_millis(500)).await;
+                        }
+                        let id = req.headers().get("x-request-id").map(|h| h.as_bytes().to_vec()).unwrap_or_default();
+                        Ok::<_, hyper::Error>(hyper::Response::new(to_body(Bytes::from(id))))
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        addr
+    }
+
+    ///записывает порядок вызовов, добавляет заголовок в ответ
+    struct Recorder(&'static str, Arc<Mutex<Vec<String>>>);
+
+    impl Middleware for Recorder
+    {
+        fn before_request(&self, request: &mut RequestParts) -> Result<(), Error>
+        {
+            let id = request.extensions.get::<RequestId>().map(|r| r.0.len()).unwrap_or_default();
+            self.1.lock().unwrap().push(format!("{} before {}", self.0, id));
+            Ok(())
+        }
+        fn after_response(&self, _request: &RequestParts, response: &mut ResponseParts) -> Result<(), Error>
+        {
+            self.1.lock().unwrap().push(format!("{} after {}", self.0, response.status.as_u16()));
+            response.headers.insert("x-rewritten", HeaderValue::from_static(self.0));
+            Ok(())
+        }
+        fn on_error(&self, _request: &RequestParts, error: &Error)
+        {
+            self.1.lock().unwrap().push(format!("{} error {}", self.0, matches!(error, Error::Timeout(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_chain()
+    {
+        let addr = server().await;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let timings = Arc::new(Mutex::new(Vec::new()));
+        let recorded = timings.clone();
+        let builder = |path: &str| HyperClient::builder(format!("http://{}{}", addr, path))
+            .with_read_timeout(Duration::from_millis(200))
+            .with_retry_count(1)
+            .with_middleware(RequestIdMiddleware::new())
+            .with_middleware(TracingMiddleware)
+            .with_middleware(Recorder("a", calls.clone()))
+            .with_middleware(Recorder("b", calls.clone()));
+        let client = builder("/")
+            .with_middleware(TimingMiddleware::new(move |request, status, elapsed| recorded.lock().unwrap().push((request.method.clone(), status, elapsed))))
+            .build()
+            .unwrap();
+        let response = client.get().await.unwrap();
+        assert_eq!(response.text().len(), 32);
+        assert_eq!(response.headers().get("x-rewritten").unwrap(), "a");
+        assert_eq!(*calls.lock().unwrap(), vec!["a before 32", "b before 32", "b after 200", "a after 200"]);
+        let timings = timings.lock().unwrap().clone();
+        assert_eq!(timings.len(), 1);
+        assert_eq!((&timings[0].0, timings[0].1), (&hyper::Method::GET, Some(StatusCode::OK)));
+        assert!(timings[0].2 < Duration::from_millis(200));
+        //заданный явно идентификатор не заменяется
+        let response = builder("/").with_header("x-request-id", "42").build().unwrap().get().await.unwrap();
+        assert_eq!(response.text(), "42");
+
+        //таймаут попытки передается в on_error
+        calls.lock().unwrap().clear();
+        let result = builder("/slow").build().unwrap().get().await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+        assert_eq!(*calls.lock().unwrap(), vec!["a before 32", "b before 32", "b error true", "a error true"]);
+    }
+}
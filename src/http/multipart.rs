@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+use hyper::{body::Bytes, header::HeaderValue};
+use rand::Rng;
+use super::body::{BodyPart, RequestBody};
+use crate::error::Error;
+
+///Тело `multipart/form-data` из текстовых полей и файлов,
+///файлы не читаются в память, а передаются с диска частями при отправке запроса
+///```ignore
+///let form = Multipart::new()
+///    .text("title", "Отчет")
+///    .file("document", "/tmp/report.pdf")
+///    .file_as("scan", "/tmp/scan.bin", "скан.jpg", "image/jpeg");
+///let response = client.post_multipart(form).await?;
+///```
+#[derive(Debug, Clone)]
+pub struct Multipart
+{
+    boundary: String,
+    fields: Vec<Field>
+}
+
+#[derive(Debug, Clone)]
+enum Field
+{
+    Text
+    {
+        name: String,
+        value: String
+    },
+    File
+    {
+        name: String,
+        path: PathBuf,
+        filename: String,
+        content_type: String
+    }
+}
+
+impl Default for Multipart
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl Multipart
+{
+    pub fn new() -> Self
+    {
+        let mut rng = rand::rng();
+        let boundary = format!("----utilites{:016x}{:016x}", rng.random::<u64>(), rng.random::<u64>());
+        Self { boundary, fields: Vec::new() }
+    }
+    pub fn text<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self
+    {
+        self.fields.push(Field::Text { name: name.into(), value: value.into() });
+        self
+    }
+    ///файл с именем из пути и типом `application/octet-stream`
+    pub fn file<N: Into<String>, P: AsRef<Path>>(self, name: N, path: P) -> Self
+    {
+        let filename = path.as_ref().file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+        self.file_as(name, path, filename, "application/octet-stream")
+    }
+    ///файл с заданными именем для сервера и типом содержимого
+    pub fn file_as<N, P, F, C>(mut self, name: N, path: P, filename: F, content_type: C) -> Self
+    where N: Into<String>, P: AsRef<Path>, F: Into<String>, C: Into<String>
+    {
+        self.fields.push(Field::File
+        {
+            name: name.into(),
+            path: path.as_ref().to_path_buf(),
+            filename: filename.into(),
+            content_type: content_type.into()
+        });
+        self
+    }
+    pub fn boundary(&self) -> &str
+    {
+        &self.boundary
+    }
+    ///подготовка тела, для файлов запоминается размер чтобы отправить Content-Length
+    pub(crate) async fn into_body(self) -> Result<RequestBody, Error>
+    {
+        let mut parts = Vec::new();
+        let mut head = String::new();
+        for field in self.fields
+        {
+            head.push_str(&format!("--{}\r\n", self.boundary));
+            match field
+            {
+                Field::Text { name, value } =>
+                {
+                    head.push_str(&format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", escape(&name), value));
+                },
+                Field::File { name, path, filename, content_type } =>
+                {
+                    let metadata = tokio::fs::metadata(&path).await.map_err(|e| Error::FileOpenError(path.display().to_string(), e.to_string()))?;
+                    if !metadata.is_file()
+                    {
+                        return Err(Error::FileOpenError(path.display().to_string(), "не является файлом".to_owned()));
+                    }
+                    head.push_str(&format!("Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n", escape(&name), escape(&filename)));
+                    head.push_str(&format!("Content-Type: {}\r\n\r\n", content_type.replace(['\r', '\n'], "")));
+                    parts.push(BodyPart::Bytes(Bytes::from(std::mem::take(&mut head))));
+                    parts.push(BodyPart::File(path, metadata.len()));
+                    head.push_str("\r\n");
+                }
+            }
+        }
+        head.push_str(&format!("--{}--\r\n", self.boundary));
+        parts.push(BodyPart::Bytes(Bytes::from(head)));
+        let content_type = HeaderValue::from_str(&format!("multipart/form-data; boundary={}", self.boundary)).map_err(hyper::http::Error::from)?;
+        Ok(RequestBody::new(parts, content_type))
+    }
+}
+
+///кавычки и переводы строк в именах полей и файлов кодируются как в браузерах
+fn escape(value: &str) -> String
+{
+    value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
@@ -0,0 +1,184 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::Duration};
+use hyper::Uri;
+use tokio::{sync::{OwnedSemaphorePermit, Semaphore}, time::Instant};
+
+///Ограничение частоты запросов (token bucket) и количества одновременных запросов к хосту,
+///состояние общее для всех клонов `HyperClient`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RateLimiter
+{
+    ///запросов в секунду и размер пачки запросов которые можно выполнить без ожидания
+    rate: Option<(f64, u32)>,
+    max_in_flight: Option<usize>,
+    hosts: Arc<Mutex<HashMap<String, HostLimits>>>
+}
+
+#[derive(Debug)]
+struct HostLimits
+{
+    ///может быть отрицательным: токены уже обещаны ожидающим запросам
+    tokens: f64,
+    updated: Instant,
+    in_flight: Option<Arc<Semaphore>>
+}
+
+impl RateLimiter
+{
+    pub(crate) fn set_rate(&mut self, per_second: f64, burst: u32)
+    {
+        self.rate = Some((per_second, burst));
+    }
+    pub(crate) fn set_max_in_flight(&mut self, max: usize)
+    {
+        self.max_in_flight = Some(max);
+    }
+    pub(crate) fn validate(&self) -> Result<(), String>
+    {
+        if let Some((per_second, burst)) = self.rate
+        {
+            if !per_second.is_finite() || per_second <= 0.0
+            {
+                return Err(format!("количество запросов в секунду должно быть больше нуля, указано {}", per_second));
+            }
+            if burst == 0
+            {
+                return Err("размер пачки запросов должен быть больше нуля".to_owned());
+            }
+        }
+        if self.max_in_flight == Some(0)
+        {
+            return Err("количество одновременных запросов к хосту должно быть больше нуля".to_owned());
+        }
+        Ok(())
+    }
+    ///ожидание свободного места и токена перед попыткой запроса,
+    ///место освобождается когда возвращенное значение удаляется
+    pub(crate) async fn acquire(&self, uri: &Uri) -> Option<OwnedSemaphorePermit>
+    {
+        if self.rate.is_none() && self.max_in_flight.is_none()
+        {
+            return None;
+        }
+        let host = uri.authority().map(|a| a.as_str().to_ascii_lowercase()).unwrap_or_default();
+        let semaphore = self.host(&host, |h| h.in_flight.clone());
+        let permit = match semaphore
+        {
+            //семафор не закрывается, ошибки быть не может
+            Some(s) => s.acquire_owned().await.ok(),
+            None => None
+        };
+        if let Some((per_second, burst)) = self.rate
+        {
+            let wait = self.host(&host, |h|
+            {
+                let now = Instant::now();
+                h.tokens = (h.tokens + now.duration_since(h.updated).as_secs_f64() * per_second).min(burst as f64);
+                h.updated = now;
+                h.tokens -= 1.0;
+                if h.tokens < 0.0 { Duration::from_secs_f64(-h.tokens / per_second) } else { Duration::ZERO }
+            });
+            if !wait.is_zero()
+            {
+                tracing::debug!("Ограничение частоты запросов к {}, ожидание {:?}", host, wait);
+                //если ожидание прервано (таймаут, отмена), обещанный токен возвращается
+                let refund = Refund { limiter: self, host: &host, burst };
+                tokio::time::sleep(wait).await;
+                std::mem::forget(refund);
+            }
+        }
+        permit
+    }
+    fn host<T>(&self, host: &str, f: impl FnOnce(&mut HostLimits) -> T) -> T
+    {
+        let mut hosts = self.hosts.lock().unwrap();
+        let limits = hosts.entry(host.to_owned()).or_insert_with(|| HostLimits
+        {
+            tokens: self.rate.map(|r| r.1 as f64).unwrap_or_default(),
+            updated: Instant::now(),
+            in_flight: self.max_in_flight.map(|m| Arc::new(Semaphore::new(m)))
+        });
+        f(limits)
+    }
+}
+
+///возврат токена запросу, ожидание которого прервано
+struct Refund<'a>
+{
+    limiter: &'a RateLimiter,
+    host: &'a str,
+    burst: u32
+}
+
+impl Drop for Refund<'_>
+{
+    fn drop(&mut self)
+    {
+        let burst = self.burst;
+        self.limiter.host(self.host, |h| h.tokens = (h.tokens + 1.0).min(burst as f64));
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{sync::{atomic::{AtomicUsize, Ordering}, Arc}, time::Duration};
+    use hyper::Uri;
+    use tokio::time::Instant;
+    use super::RateLimiter;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_token_bucket()
+    {
+        let mut limiter = RateLimiter::default();
+        limiter.set_rate(10.0, 3);
+        let uri: Uri = "http://example.com/a".parse().unwrap();
+        let started = Instant::now();
+        //пачка из трех запросов без ожидания, далее по одному каждые 100мс
+        for _ in 0..3
+        {
+            limiter.acquire(&uri).await;
+        }
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        limiter.acquire(&uri).await;
+        limiter.acquire(&uri).await;
+        assert_eq!(started.elapsed(), Duration::from_millis(200));
+        //другой хост ограничивается отдельно, клоны разделяют состояние
+        let other = limiter.clone();
+        other.acquire(&"http://other.com/".parse().unwrap()).await;
+        assert_eq!(started.elapsed(), Duration::from_millis(200));
+        other.acquire(&uri).await;
+        assert_eq!(started.elapsed(), Duration::from_millis(300));
+        //прерванное ожидание возвращает токен, следующий запрос не ждет за него
+        assert!(tokio::time::timeout(Duration::from_millis(50), limiter.acquire(&uri)).await.is_err());
+        limiter.acquire(&uri).await;
+        assert_eq!(started.elapsed(), Duration::from_millis(400));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_in_flight()
+    {
+        let mut limiter = RateLimiter::default();
+        limiter.set_max_in_flight(2);
+        assert!(limiter.validate().is_ok());
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+        let tasks = (0..6).map(|_|
+        {
+            let (limiter, in_flight, max) = (limiter.clone(), in_flight.clone(), max.clone());
+            tokio::spawn(async move
+            {
+                let _permit = limiter.acquire(&"http://example.com/".parse().unwrap()).await;
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+            })
+        }).collect::<Vec<_>>();
+        let started = Instant::now();
+        futures::future::join_all(tasks).await;
+        assert_eq!(max.load(Ordering::SeqCst), 2);
+        assert_eq!(started.elapsed(), Duration::from_millis(300));
+        limiter.set_max_in_flight(0);
+        assert!(limiter.validate().is_err());
+    }
+}
@@ -0,0 +1,250 @@
+use std::{fmt::Debug, sync::Arc};
+use hyper::{header::*, Method, Request, StatusCode, Uri};
+use crate::error::Error;
+
+///Заголовки которые не передаются при перенаправлении на другой хост
+const SENSITIVE_HEADERS: [HeaderName; 5] = [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, WWW_AUTHENTICATE, HOST];
+
+///Правило выполнения перенаправлений (статусы 301, 302, 303, 307, 308)
+#[derive(Clone)]
+pub enum RedirectPolicy
+{
+    ///перенаправления не выполняются, возвращается ответ 3xx
+    None,
+    ///выполняется не более указанного количества перенаправлений, при превышении возвращается ошибка
+    Limited(usize),
+    ///решение о каждом перенаправлении принимает функция
+    Custom(Arc<dyn Fn(&RedirectAttempt) -> RedirectAction + Send + Sync>)
+}
+
+impl Default for RedirectPolicy
+{
+    fn default() -> Self
+    {
+        RedirectPolicy::Limited(10)
+    }
+}
+
+impl Debug for RedirectPolicy
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            RedirectPolicy::None => f.write_str("None"),
+            RedirectPolicy::Limited(n) => f.debug_tuple("Limited").field(n).finish(),
+            RedirectPolicy::Custom(_) => f.write_str("Custom")
+        }
+    }
+}
+
+///Информация о перенаправлении для `RedirectPolicy::Custom`
+#[derive(Debug)]
+pub struct RedirectAttempt<'a>
+{
+    status: StatusCode,
+    next: &'a Uri,
+    previous: &'a [Uri]
+}
+impl<'a> RedirectAttempt<'a>
+{
+    pub fn status(&self) -> StatusCode
+    {
+        self.status
+    }
+    ///адрес на который выполняется перенаправление
+    pub fn url(&self) -> &Uri
+    {
+        self.next
+    }
+    ///адреса пройденные до текущего перенаправления, начиная с исходного
+    pub fn previous(&self) -> &[Uri]
+    {
+        self.previous
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RedirectAction
+{
+    Follow,
+    ///вернуть текущий ответ 3xx без перенаправления
+    Stop,
+    Error(String)
+}
+
+impl RedirectPolicy
+{
+    pub fn custom<F>(f: F) -> Self
+    where F: Fn(&RedirectAttempt) -> RedirectAction + Send + Sync + 'static
+    {
+        RedirectPolicy::Custom(Arc::new(f))
+    }
+    ///Подготовка запроса для перенаправления по ответу `status` с заголовками `headers` на запрос `current`
+    ///`previous` - адреса пройденные до `current`
+    ///`Ok(None)` - перенаправление не выполняется, нужно вернуть текущий ответ
+    pub(crate) fn next_request<B: Clone + Default>(&self, status: StatusCode, headers: &HeaderMap, current: &Request<B>, previous: &[Uri]) -> Result<Option<Request<B>>, Error>
+    {
+        if !matches!(status.as_u16(), 301 | 302 | 303 | 307 | 308)
+        {
+            return Ok(None);
+        }
+        let location = match headers.get(LOCATION).and_then(|l| l.to_str().ok())
+        {
+            Some(l) => l,
+            None => return Ok(None)
+        };
+        //без перенаправлений Location не разбирается, ответ возвращается как есть
+        let custom = match self
+        {
+            RedirectPolicy::None => return Ok(None),
+            RedirectPolicy::Limited(max) =>
+            {
+                if previous.len() >= *max
+                {
+                    return Err(Error::TooManyRedirects(resolve(current.uri(), location)?.to_string()));
+                }
+                None
+            },
+            RedirectPolicy::Custom(f) => Some(f)
+        };
+        let next = resolve(current.uri(), location)?;
+        if let Some(f) = custom
+        {
+            let mut visited = previous.to_vec();
+            visited.push(current.uri().clone());
+            let attempt = RedirectAttempt { status, next: &next, previous: &visited };
+            match f(&attempt)
+            {
+                RedirectAction::Follow => (),
+                RedirectAction::Stop => return Ok(None),
+                RedirectAction::Error(e) => return Err(Error::RedirectError(e))
+            }
+        }
+        tracing::debug!("Перенаправление {} с {} на {}", status, current.uri(), &next);
+        //303 всегда выполняется через GET, 301 и 302 для POST так же меняются на GET как в браузерах
+        let to_get = match status.as_u16()
+        {
+            303 => current.method() != Method::HEAD,
+            301 | 302 => current.method() == Method::POST,
+            _ => false
+        };
+        let mut req = Request::new(if to_get { B::default() } else { current.body().clone() });
+        *req.method_mut() = if to_get { Method::GET } else { current.method().clone() };
+        *req.version_mut() = current.version();
+        *req.headers_mut() = current.headers().clone();
+        if to_get
+        {
+            for h in [CONTENT_TYPE, CONTENT_LENGTH, CONTENT_ENCODING, TRANSFER_ENCODING]
+            {
+                req.headers_mut().remove(h);
+            }
+        }
+        if !same_host(current.uri(), &next)
+        {
+            for h in &SENSITIVE_HEADERS
+            {
+                req.headers_mut().remove(h);
+            }
+        }
+        *req.uri_mut() = next;
+        Ok(Some(req))
+    }
+}
+
+///разрешение значения Location (в том числе относительного) относительно текущего адреса
+fn resolve(current: &Uri, location: &str) -> Result<Uri, Error>
+{
+    let base = url::Url::parse(&current.to_string()).map_err(|e| Error::RedirectError(format!("{} -> {}", current, e)))?;
+    let next = base.join(location).map_err(|e| Error::RedirectError(format!("{} -> {}", location, e)))?;
+    next.as_str().parse::<Uri>().map_err(|e| Error::RedirectError(format!("{} -> {}", next, e)))
+}
+
+///совпадение схемы, хоста и порта (с учетом порта схемы по умолчанию),
+///при переходе с https на http заголовки авторизации тоже удаляются
+fn same_host(a: &Uri, b: &Uri) -> bool
+{
+    let origin = |u: &Uri|
+    {
+        let scheme = u.scheme_str().map(|s| s.to_ascii_lowercase());
+        let port = u.port_u16().or(match scheme.as_deref()
+        {
+            Some("https") => Some(443),
+            Some("http") => Some(80),
+            _ => None
+        });
+        (scheme, u.host().map(|h| h.to_ascii_lowercase()), port)
+    };
+    origin(a) == origin(b)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use hyper::{body::Bytes, header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, LOCATION}, Method, Request, StatusCode, Uri};
+    use super::{RedirectAction, RedirectPolicy};
+
+    fn request(method: Method, uri: &str) -> Request<Bytes>
+    {
+        let mut req = Request::new(Bytes::from_static(b"{\"id\":1}"));
+        *req.method_mut() = method;
+        *req.uri_mut() = uri.parse().unwrap();
+        req.headers_mut().insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        req.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        req
+    }
+    fn location(l: &'static str) -> HeaderMap
+    {
+        let mut h = HeaderMap::new();
+        h.insert(LOCATION, HeaderValue::from_static(l));
+        h
+    }
+
+    #[test]
+    fn test_redirect_methods()
+    {
+        let policy = RedirectPolicy::default();
+        let req = request(Method::POST, "http://example.com/a/b?x=1");
+        let next = policy.next_request(StatusCode::SEE_OTHER, &location("c"), &req, &[]).unwrap().unwrap();
+        assert_eq!(next.method(), Method::GET);
+        assert_eq!(next.uri(), "http://example.com/a/c");
+        assert!(next.body().is_empty());
+        assert!(next.headers().get(CONTENT_TYPE).is_none());
+        assert!(next.headers().get(AUTHORIZATION).is_some());
+
+        let next = policy.next_request(StatusCode::PERMANENT_REDIRECT, &location("/d"), &req, &[]).unwrap().unwrap();
+        assert_eq!(next.method(), Method::POST);
+        assert_eq!(next.uri(), "http://example.com/d");
+        assert_eq!(next.body(), req.body());
+
+        let put = request(Method::PUT, "http://example.com/");
+        let next = policy.next_request(StatusCode::FOUND, &location("https://other.com/e"), &put, &[]).unwrap().unwrap();
+        assert_eq!(next.method(), Method::PUT);
+        assert!(next.headers().get(AUTHORIZATION).is_none());
+        //переход с https на http того же хоста не передает авторизацию в открытом виде
+        let secure = request(Method::GET, "https://example.com/");
+        let next = policy.next_request(StatusCode::FOUND, &location("http://example.com/e"), &secure, &[]).unwrap().unwrap();
+        assert!(next.headers().get(AUTHORIZATION).is_none());
+        //явно указанный порт по умолчанию и регистр хоста не меняют адрес
+        let next = policy.next_request(StatusCode::FOUND, &location("http://EXAMPLE.com:80/e"), &put, &[]).unwrap().unwrap();
+        assert!(next.headers().get(AUTHORIZATION).is_some());
+
+        assert!(policy.next_request(StatusCode::NOT_MODIFIED, &location("/f"), &req, &[]).unwrap().is_none());
+        assert!(RedirectPolicy::None.next_request(StatusCode::FOUND, &location("/f"), &req, &[]).unwrap().is_none());
+        //некорректный Location не важен если перенаправления отключены
+        assert!(RedirectPolicy::None.next_request(StatusCode::FOUND, &location("http://[bad"), &req, &[]).unwrap().is_none());
+        assert!(policy.next_request(StatusCode::FOUND, &location("http://[bad"), &req, &[]).is_err());
+    }
+
+    #[test]
+    fn test_redirect_limits()
+    {
+        let req = request(Method::GET, "http://example.com/");
+        let previous: Vec<Uri> = vec!["http://example.com/1".parse().unwrap(), "http://example.com/2".parse().unwrap()];
+        assert!(RedirectPolicy::Limited(2).next_request(StatusCode::FOUND, &location("/3"), &req, &previous).is_err());
+        assert!(RedirectPolicy::Limited(3).next_request(StatusCode::FOUND, &location("/3"), &req, &previous).unwrap().is_some());
+        let only_same_host = RedirectPolicy::custom(|a| if a.url().host() == Some("example.com") { RedirectAction::Follow } else { RedirectAction::Stop });
+        assert!(only_same_host.next_request(StatusCode::FOUND, &location("/3"), &req, &previous).unwrap().is_some());
+        assert!(only_same_host.next_request(StatusCode::FOUND, &location("http://other.com/"), &req, &previous).unwrap().is_none());
+    }
+}
@@ -0,0 +1,321 @@
+use std::{pin::Pin, time::{Duration, Instant}};
+use futures::{Stream, StreamExt};
+use hyper::{body::Bytes, header::{CONTENT_LENGTH, CONTENT_TYPE}, HeaderMap, StatusCode, Uri, Version};
+use serde::de::DeserializeOwned;
+use crate::error::Error;
+
+///Поток частей тела ответа
+pub type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>>;
+
+///Максимальная длина тела ответа в тексте ошибки
+const ERROR_BODY_LEN: usize = 512;
+
+///Ответ сервера на запрос `HyperClient`
+#[derive(Debug, Clone)]
+pub struct ClientResponse
+{
+    pub(crate) status: StatusCode,
+    pub(crate) version: Version,
+    pub(crate) headers: HeaderMap,
+    pub(crate) url: Uri,
+    pub(crate) redirects: Vec<Uri>,
+    pub(crate) elapsed: Duration,
+    pub(crate) from_cache: bool,
+    pub(crate) body: Bytes
+}
+
+impl ClientResponse
+{
+    pub fn status(&self) -> StatusCode
+    {
+        self.status
+    }
+    ///версия протокола по которой получен ответ
+    pub fn version(&self) -> Version
+    {
+        self.version
+    }
+    pub fn headers(&self) -> &HeaderMap
+    {
+        &self.headers
+    }
+    ///адрес с которого получен ответ, после всех перенаправлений
+    pub fn url(&self) -> &Uri
+    {
+        &self.url
+    }
+    ///адреса вернувшие перенаправление, начиная с исходного
+    pub fn redirects(&self) -> &[Uri]
+    {
+        &self.redirects
+    }
+    ///время от отправки запроса до получения всего тела ответа (последней попытки)
+    pub fn elapsed(&self) -> Duration
+    {
+        self.elapsed
+    }
+    ///ответ взят из `HttpCache`: без запроса к серверу или после ответа 304 Not Modified
+    pub fn from_cache(&self) -> bool
+    {
+        self.from_cache
+    }
+    pub fn bytes(&self) -> &Bytes
+    {
+        &self.body
+    }
+    pub fn into_bytes(self) -> Bytes
+    {
+        self.body
+    }
+    ///кодировка из заголовка Content-Type
+    pub fn charset(&self) -> Option<&str>
+    {
+        self.headers
+        .get(CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .and_then(|ct| ct
+            .split(';')
+            .filter_map(|p| p.split_once('='))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case("charset"))
+            .map(|(_, v)| v.trim().trim_matches('"')))
+    }
+    ///Тело ответа в виде строки в кодировке из Content-Type
+    ///если кодировка не указана и тело не является utf-8, то с фичей `encoding` тело читается как windows-1251
+    pub fn text(&self) -> String
+    {
+        decode_text(&self.body, self.charset())
+    }
+    ///Разбор тела JSON, ошибка `Error::JsonError` содержит статус и начало тела ответа
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, Error>
+    {
+        serde_json::from_slice(&self.body).map_err(|error| Error::JsonError
+        {
+            url: self.url.to_string(),
+            status: self.status,
+            error,
+            body: truncate(self.text(), ERROR_BODY_LEN)
+        })
+    }
+    pub fn is_success(&self) -> bool
+    {
+        self.status.is_success()
+    }
+    ///Ошибка `Error::StatusError` если статус ответа 4xx или 5xx
+    pub fn error_for_status(self) -> Result<Self, Error>
+    {
+        if self.status.is_client_error() || self.status.is_server_error()
+        {
+            Err(self.status_error())
+        }
+        else
+        {
+            Ok(self)
+        }
+    }
+    pub(crate) fn status_error(&self) -> Error
+    {
+        Error::StatusError
+        {
+            url: self.url.to_string(),
+            status: self.status,
+            body: truncate(self.text(), ERROR_BODY_LEN)
+        }
+    }
+}
+
+///Ответ сервера с еще не прочитанным телом
+pub struct StreamingResponse
+{
+    pub(crate) status: StatusCode,
+    pub(crate) version: Version,
+    pub(crate) headers: HeaderMap,
+    pub(crate) url: Uri,
+    pub(crate) redirects: Vec<Uri>,
+    pub(crate) started: Instant,
+    pub(crate) body: BodyStream
+}
+
+impl std::fmt::Debug for StreamingResponse
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        f.debug_struct("StreamingResponse")
+        .field("status", &self.status)
+        .field("version", &self.version)
+        .field("headers", &self.headers)
+        .field("url", &self.url)
+        .field("redirects", &self.redirects)
+        .finish()
+    }
+}
+
+impl StreamingResponse
+{
+    pub fn status(&self) -> StatusCode
+    {
+        self.status
+    }
+    ///версия протокола по которой получен ответ
+    pub fn version(&self) -> Version
+    {
+        self.version
+    }
+    pub fn headers(&self) -> &HeaderMap
+    {
+        &self.headers
+    }
+    ///адрес с которого получен ответ, после всех перенаправлений
+    pub fn url(&self) -> &Uri
+    {
+        &self.url
+    }
+    ///адреса вернувшие перенаправление, начиная с исходного
+    pub fn redirects(&self) -> &[Uri]
+    {
+        &self.redirects
+    }
+    ///размер тела из заголовка Content-Length
+    pub fn content_length(&self) -> Option<u64>
+    {
+        self.headers
+        .get(CONTENT_LENGTH)
+        .and_then(|l| l.to_str().ok())
+        .and_then(|l| l.parse().ok())
+    }
+    ///тело ответа в виде потока частей
+    pub fn bytes_stream(self) -> BodyStream
+    {
+        self.body
+    }
+    ///чтение всего тела ответа в память
+    pub async fn collect(mut self) -> Result<ClientResponse, Error>
+    {
+        let mut body = Vec::with_capacity(self.content_length().unwrap_or_default().min(1 << 20) as usize);
+        while let Some(chunk) = self.body.next().await
+        {
+            body.extend_from_slice(&chunk?);
+        }
+        Ok(ClientResponse
+        {
+            status: self.status,
+            version: self.version,
+            headers: self.headers,
+            url: self.url,
+            redirects: self.redirects,
+            elapsed: self.started.elapsed(),
+            from_cache: false,
+            body: Bytes::from(body)
+        })
+    }
+}
+
+fn truncate(mut text: String, max: usize) -> String
+{
+    if text.len() > max
+    {
+        let mut end = max;
+        while !text.is_char_boundary(end)
+        {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push('…');
+    }
+    text
+}
+
+#[cfg(feature="encoding")]
+fn decode_text(body: &[u8], charset: Option<&str>) -> String
+{
+    use encoding::{all::WINDOWS_1251, label::encoding_from_whatwg_label, DecoderTrap, Encoding};
+    match charset.filter(|c| !c.eq_ignore_ascii_case("utf-8") && !c.eq_ignore_ascii_case("utf8"))
+    {
+        Some(c) =>
+        {
+            if let Some(enc) = encoding_from_whatwg_label(c)
+            {
+                if let Ok(text) = enc.decode(body, DecoderTrap::Replace)
+                {
+                    return text;
+                }
+            }
+            String::from_utf8_lossy(body).into_owned()
+        },
+        None => match std::str::from_utf8(body)
+        {
+            Ok(text) => text.to_owned(),
+            Err(_) if charset.is_none() => WINDOWS_1251
+                .decode(body, DecoderTrap::Replace)
+                .unwrap_or_else(|_| String::from_utf8_lossy(body).into_owned()),
+            Err(_) => String::from_utf8_lossy(body).into_owned()
+        }
+    }
+}
+#[cfg(not(feature="encoding"))]
+fn decode_text(body: &[u8], _charset: Option<&str>) -> String
+{
+    String::from_utf8_lossy(body).into_owned()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::time::Duration;
+    use hyper::{body::Bytes, header::{HeaderValue, CONTENT_TYPE}, HeaderMap, StatusCode, Version};
+    use super::ClientResponse;
+
+    fn response(status: StatusCode, content_type: &'static str, body: Bytes) -> ClientResponse
+    {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        ClientResponse
+        {
+            status,
+            version: Version::HTTP_11,
+            headers,
+            url: "http://example.com/".parse().unwrap(),
+            redirects: Vec::new(),
+            elapsed: Duration::ZERO,
+            from_cache: false,
+            body
+        }
+    }
+
+    #[test]
+    fn test_response_text_and_json()
+    {
+        let r = response(StatusCode::OK, "application/json; charset=\"UTF-8\"", Bytes::from("{\"name\":\"тест\"}"));
+        assert_eq!(r.charset(), Some("UTF-8"));
+        assert_eq!(r.text(), "{\"name\":\"тест\"}");
+        let v: serde_json::Value = r.json().unwrap();
+        assert_eq!(v["name"], "тест");
+        assert!(r.error_for_status().is_ok());
+    }
+
+    #[test]
+    fn test_response_error_for_status()
+    {
+        let body = Bytes::from("ошибка ".repeat(100));
+        let r = response(StatusCode::SERVICE_UNAVAILABLE, "text/plain", body);
+        match r.error_for_status()
+        {
+            Err(crate::error::Error::StatusError { status, body, .. }) =>
+            {
+                assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+                assert!(body.len() <= 512 + '…'.len_utf8());
+                assert!(body.ends_with('…'));
+            },
+            _ => panic!("ожидалась ошибка статуса")
+        }
+    }
+
+    #[cfg(feature="encoding")]
+    #[test]
+    fn test_response_text_windows_1251()
+    {
+        //"Привет" в windows-1251
+        let cp1251 = Bytes::from_static(&[0xCF, 0xF0, 0xE8, 0xE2, 0xE5, 0xF2]);
+        assert_eq!(response(StatusCode::OK, "text/html; charset=windows-1251", cp1251.clone()).text(), "Привет");
+        assert_eq!(response(StatusCode::OK, "text/html", cp1251).text(), "Привет");
+    }
+}
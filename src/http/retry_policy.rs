@@ -0,0 +1,293 @@
+use std::{error::Error as StdError, time::{Duration, SystemTime, UNIX_EPOCH}};
+use hyper::{header::{HeaderMap, RETRY_AFTER}, Method, StatusCode};
+use super::{ClientResponse, StreamingResponse};
+use crate::error::Error;
+
+///Правило повтора запросов `HyperClient`
+///повторяются ошибки подключения, таймауты, обрывы соединения и ответы с указанными статусами,
+///ошибки DNS, TLS, перенаправлений и прочие постоянные ошибки не повторяются
+#[derive(Debug, Clone)]
+pub struct RetryPolicy
+{
+    statuses: Vec<StatusCode>,
+    non_idempotent: bool,
+    max_retry_after: Duration
+}
+
+impl Default for RetryPolicy
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            statuses: vec![
+                StatusCode::REQUEST_TIMEOUT,
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT
+            ],
+            non_idempotent: false,
+            max_retry_after: Duration::from_secs(60)
+        }
+    }
+}
+
+///Решение по результату попытки
+#[derive(Debug, PartialEq)]
+pub(crate) enum RetryDecision
+{
+    Stop,
+    ///повтор после обычной задержки
+    Retry,
+    ///повтор после задержки из заголовка Retry-After
+    RetryAfter(Duration)
+}
+
+///Результат запроса, по статусу которого может потребоваться повтор
+pub(crate) trait RetryResponse
+{
+    fn retry_status(&self) -> Option<(StatusCode, &HeaderMap)>;
+}
+impl RetryResponse for ClientResponse
+{
+    fn retry_status(&self) -> Option<(StatusCode, &HeaderMap)>
+    {
+        Some((self.status, &self.headers))
+    }
+}
+impl RetryResponse for StreamingResponse
+{
+    fn retry_status(&self) -> Option<(StatusCode, &HeaderMap)>
+    {
+        Some((self.status, &self.headers))
+    }
+}
+impl RetryResponse for u64
+{
+    fn retry_status(&self) -> Option<(StatusCode, &HeaderMap)>
+    {
+        None
+    }
+}
+
+impl RetryPolicy
+{
+    ///статусы ответа при которых запрос повторяется, по умолчанию 408, 429, 502, 503, 504
+    pub fn with_statuses(mut self, statuses: Vec<StatusCode>) -> Self
+    {
+        self.statuses = statuses;
+        self
+    }
+    ///повторять неидемпотентные запросы (POST, PATCH), по умолчанию не повторяются
+    pub fn with_non_idempotent(mut self, retry: bool) -> Self
+    {
+        self.non_idempotent = retry;
+        self
+    }
+    ///максимальное ожидание по заголовку Retry-After, если сервер просит ждать дольше, запрос не повторяется
+    ///по умолчанию 60 секунд
+    pub fn with_max_retry_after(mut self, max: Duration) -> Self
+    {
+        self.max_retry_after = max;
+        self
+    }
+    pub fn statuses(&self) -> &[StatusCode]
+    {
+        &self.statuses
+    }
+    ///нужно ли повторять запрос `method` после результата `result`
+    pub(crate) fn decide<T: RetryResponse>(&self, method: &Method, result: &Result<T, Error>) -> RetryDecision
+    {
+        if !self.non_idempotent && !is_idempotent(method)
+        {
+            return RetryDecision::Stop;
+        }
+        match result
+        {
+            Ok(response) => match response.retry_status()
+            {
+                Some((status, headers)) => self.decide_status(status, Some(headers)),
+                None => RetryDecision::Stop
+            },
+            Err(Error::StatusError { status, .. }) => self.decide_status(*status, None),
+            Err(e) if is_transient(e) => RetryDecision::Retry,
+            Err(_) => RetryDecision::Stop
+        }
+    }
+    fn decide_status(&self, status: StatusCode, headers: Option<&HeaderMap>) -> RetryDecision
+    {
+        if !self.statuses.contains(&status)
+        {
+            return RetryDecision::Stop;
+        }
+        match headers.and_then(retry_after)
+        {
+            Some(delay) if delay > self.max_retry_after => RetryDecision::Stop,
+            Some(delay) => RetryDecision::RetryAfter(delay),
+            None => RetryDecision::Retry
+        }
+    }
+}
+
+///GET, HEAD, OPTIONS, TRACE, PUT и DELETE по RFC 9110 9.2.2
+fn is_idempotent(method: &Method) -> bool
+{
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE)
+}
+
+///Задержка из заголовка Retry-After, задается в секундах или датой
+fn retry_after(headers: &HeaderMap) -> Option<Duration>
+{
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>()
+    {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = super::cookie::parse_cookie_date(value)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    Some(Duration::from_secs(date.saturating_sub(now)))
+}
+
+///Временная ошибка, после которой запрос имеет смысл повторить
+fn is_transient(error: &Error) -> bool
+{
+    match error
+    {
+        Error::Timeout(_) | Error::DownloadSizeError(..) | Error::RangeError(_) => true,
+        Error::IoError(e) => is_transient_io(e),
+        Error::HyperError(e) => is_transient_hyper(e),
+        Error::HttpClientLegacyError(e) =>
+        {
+            if is_permanent_connect(e)
+            {
+                return false;
+            }
+            if e.is_connect()
+            {
+                return true;
+            }
+            //ошибка после подключения, например соединение из пула закрыто сервером
+            for s in sources(e)
+            {
+                if let Some(h) = s.downcast_ref::<hyper::Error>()
+                {
+                    return is_transient_hyper(h);
+                }
+                if let Some(io) = s.downcast_ref::<std::io::Error>().filter(|io| io.get_ref().is_none())
+                {
+                    return is_transient_io(io);
+                }
+            }
+            false
+        },
+        _ => false
+    }
+}
+
+fn is_transient_hyper(e: &hyper::Error) -> bool
+{
+    e.is_incomplete_message() || e.is_canceled() || e.is_closed() || e.is_timeout()
+    || e.source().and_then(|s| s.downcast_ref::<std::io::Error>()).is_some_and(is_transient_io)
+}
+
+fn is_transient_io(e: &std::io::Error) -> bool
+{
+    use std::io::ErrorKind::*;
+    matches!(e.kind(), ConnectionRefused | ConnectionReset | ConnectionAborted | NotConnected | BrokenPipe | TimedOut | Interrupted | UnexpectedEof)
+}
+
+///ошибка разрешения имени или TLS (сертификат, рукопожатие), повтор не поможет
+fn is_permanent_connect(e: &hyper_util::client::legacy::Error) -> bool
+{
+    sources(e).any(|s| s.downcast_ref::<rustls::Error>().is_some() || s.downcast_ref::<super::proxy::DnsError>().is_some())
+}
+
+///цепочка вложенных ошибок, начиная с `e.source()`
+///`io::Error::source()` пропускает вложенную ошибку, поэтому для него берется `get_ref()`
+fn sources<'a>(e: &'a (dyn StdError + 'static)) -> impl Iterator<Item = &'a (dyn StdError + 'static)>
+{
+    std::iter::successors(e.source(), |&s| match s.downcast_ref::<std::io::Error>()
+    {
+        Some(io) => io.get_ref().map(|inner| inner as &(dyn StdError + 'static)),
+        None => s.source()
+    })
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::time::{Duration, SystemTime};
+    use hyper::{header::{HeaderMap, HeaderValue, RETRY_AFTER}, Method, StatusCode};
+    use super::{RetryDecision, RetryPolicy};
+    use crate::error::Error;
+
+    fn status_error(status: StatusCode) -> Result<u64, Error>
+    {
+        Err(Error::StatusError { url: "http://example.com/".to_owned(), status, body: String::new() })
+    }
+
+    #[test]
+    fn test_retry_policy_decide()
+    {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.decide(&Method::GET, &status_error(StatusCode::SERVICE_UNAVAILABLE)), RetryDecision::Retry);
+        assert_eq!(policy.decide(&Method::GET, &status_error(StatusCode::INTERNAL_SERVER_ERROR)), RetryDecision::Stop);
+        assert_eq!(policy.decide(&Method::GET, &status_error(StatusCode::NOT_FOUND)), RetryDecision::Stop);
+        assert_eq!(policy.decide(&Method::POST, &status_error(StatusCode::SERVICE_UNAVAILABLE)), RetryDecision::Stop);
+        assert_eq!(policy.clone().with_non_idempotent(true).decide(&Method::POST, &status_error(StatusCode::SERVICE_UNAVAILABLE)), RetryDecision::Retry);
+        let custom = policy.clone().with_statuses(vec![StatusCode::INTERNAL_SERVER_ERROR]);
+        assert_eq!(custom.decide(&Method::GET, &status_error(StatusCode::INTERNAL_SERVER_ERROR)), RetryDecision::Retry);
+        assert_eq!(custom.decide(&Method::GET, &status_error(StatusCode::SERVICE_UNAVAILABLE)), RetryDecision::Stop);
+
+        let timeout: Result<u64, Error> = Err(Error::Timeout("http://example.com/".to_owned()));
+        assert_eq!(policy.decide(&Method::PUT, &timeout), RetryDecision::Retry);
+        let reset: Result<u64, Error> = Err(std::io::Error::from(std::io::ErrorKind::ConnectionReset).into());
+        assert_eq!(policy.decide(&Method::GET, &reset), RetryDecision::Retry);
+        let denied: Result<u64, Error> = Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied).into());
+        assert_eq!(policy.decide(&Method::GET, &denied), RetryDecision::Stop);
+        let redirect: Result<u64, Error> = Err(Error::TooManyRedirects("http://example.com/".to_owned()));
+        assert_eq!(policy.decide(&Method::GET, &redirect), RetryDecision::Stop);
+    }
+
+    #[test]
+    fn test_retry_after()
+    {
+        let policy = RetryPolicy::default().with_max_retry_after(Duration::from_secs(10));
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("3"));
+        assert_eq!(policy.decide_status(StatusCode::TOO_MANY_REQUESTS, Some(&headers)), RetryDecision::RetryAfter(Duration::from_secs(3)));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(policy.decide_status(StatusCode::TOO_MANY_REQUESTS, Some(&headers)), RetryDecision::Stop);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(policy.decide_status(StatusCode::SERVICE_UNAVAILABLE, Some(&headers)), RetryDecision::RetryAfter(Duration::ZERO));
+        let future = httpdate(SystemTime::now() + Duration::from_secs(5));
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(&future).unwrap());
+        match policy.decide_status(StatusCode::SERVICE_UNAVAILABLE, Some(&headers))
+        {
+            RetryDecision::RetryAfter(d) => assert!(d >= Duration::from_secs(3) && d <= Duration::from_secs(5)),
+            d => panic!("{:?}", d)
+        }
+    }
+
+    ///дата в формате IMF-fixdate
+    fn httpdate(time: SystemTime) -> String
+    {
+        const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+        //1970-01-01 четверг
+        const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+        let secs = time.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        let (days, rest) = (secs / 86400, secs % 86400);
+        //алгоритм civil_from_days
+        let z = days as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        format!("{}, {:02} {} {} {:02}:{:02}:{:02} GMT", WEEKDAYS[(days % 7) as usize], day, MONTHS[month as usize - 1], year, rest / 3600, rest % 3600 / 60, rest % 60)
+    }
+}
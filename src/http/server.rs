@@ -0,0 +1,621 @@
+use std::{collections::HashMap, convert::Infallible, fmt::Debug, future::Future, net::SocketAddr, sync::Arc, task::{Context, Poll}, time::Duration};
+use futures::future::BoxFuture;
+use http_body_util::BodyExt;
+use hyper::{body::{Body, Bytes, Incoming}, header::{HeaderValue, ALLOW}, Method, Request, Response, StatusCode};
+use hyper_util::{rt::{TokioExecutor, TokioIo}, server::{conn::auto, graceful::GracefulShutdown}};
+use tokio::{net::{TcpListener, ToSocketAddrs}, sync::Notify, task::JoinHandle};
+use tower_http::trace::TraceLayer;
+use tower_layer::Layer;
+use tower_service::Service;
+use super::{empty_response, error_empty_response, error_response, BoxBody};
+use crate::error::Error;
+pub use tower_http::cors::CorsLayer;
+
+///Преобразование результата обработчика в ответ сервера
+pub trait IntoResponse
+{
+    fn into_response(self) -> Response<BoxBody>;
+}
+
+impl IntoResponse for Response<BoxBody>
+{
+    fn into_response(self) -> Response<BoxBody>
+    {
+        self
+    }
+}
+
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for Result<T, E>
+{
+    fn into_response(self) -> Response<BoxBody>
+    {
+        match self
+        {
+            Ok(r) => r.into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
+
+///ошибка пишется в лог, клиент получает 500 без тела: текст ошибки может содержать адреса, пути и детали ввода-вывода
+impl IntoResponse for Error
+{
+    fn into_response(self) -> Response<BoxBody>
+    {
+        tracing::error!("Ошибка обработки запроса: {:?}", self);
+        error_empty_response(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+///Ответ 204 без тела, для обработчиков не возвращающих данных
+impl IntoResponse for ()
+{
+    fn into_response(self) -> Response<BoxBody>
+    {
+        empty_response(StatusCode::NO_CONTENT)
+    }
+}
+
+type Handler<S> = Arc<dyn Fn(Request<Incoming>, S) -> BoxFuture<'static, Response<BoxBody>> + Send + Sync>;
+
+///Параметры пути `:name` и `*name` подошедшего маршрута, доступны в `Request::extensions`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathParams(HashMap<String, String>);
+
+impl PathParams
+{
+    ///значение параметра после декодирования
+    pub fn get(&self, name: &str) -> Option<&str>
+    {
+        self.0.get(name).map(|v| v.as_str())
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)>
+    {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+///для проверки обработчиков без сервера
+impl FromIterator<(String, String)> for PathParams
+{
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self
+    {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment
+{
+    Static(String),
+    Param(String),
+    ///остаток пути
+    Tail(String)
+}
+
+struct Route<S>
+{
+    method: Method,
+    segments: Vec<Segment>,
+    handler: Handler<S>
+}
+
+impl<S> Route<S>
+{
+    fn matches(&self, path: &[&str]) -> Option<PathParams>
+    {
+        let mut params = HashMap::new();
+        for (i, segment) in self.segments.iter().enumerate()
+        {
+            match segment
+            {
+                Segment::Static(s) => if path.get(i) != Some(&s.as_str())
+                {
+                    return None;
+                },
+                Segment::Param(name) =>
+                {
+                    params.insert(name.clone(), decode(path.get(i)?));
+                },
+                Segment::Tail(name) =>
+                {
+                    let tail = path[i.min(path.len())..].iter().map(|s| decode(s)).collect::<Vec<_>>();
+                    params.insert(name.clone(), tail.join("/"));
+                    return Some(PathParams(params));
+                }
+            }
+        }
+        (path.len() == self.segments.len()).then_some(PathParams(params))
+    }
+}
+
+fn decode(segment: &str) -> String
+{
+    super::decoding::decode(segment).map(|s| s.into_owned()).unwrap_or_else(|_| segment.to_owned())
+}
+
+///Маршрутизация запросов по методу и пути.
+///Путь маршрута состоит из сегментов: постоянных, параметров `:name` и остатка пути `*name` (только последним),
+///значения параметров передаются обработчику в `Request::extensions` как `PathParams` (см. `extract`).
+///Маршруты проверяются в порядке добавления, для HEAD без своего маршрута используется GET.
+///Если путь подошел, а метод нет - ответ 405 с заголовком `Allow`, если не подошел путь - `fallback` или 404
+///```ignore
+///let router = Router::new()
+///    .get("/items", |_req, db: Db| async move { json_response(&db.items().await) })
+///    .get("/items/:id", |req, db: Db| async move
+///    {
+///        let id: u64 = extract::path_param(&req, "id")?;
+///        match db.item(id)
+///        {
+///            Some(item) => Ok(json_response(&item)),
+///            None => Err(empty_response(StatusCode::NOT_FOUND))
+///        }
+///    })
+///    .get("/static/*file", |req, _| async move { ... });
+///```
+pub struct Router<S = ()>
+{
+    routes: Vec<Route<S>>,
+    fallback: Option<Handler<S>>
+}
+
+impl<S> Debug for Router<S>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        let routes = self.routes.iter().map(|r| (&r.method, &r.segments)).collect::<Vec<_>>();
+        f.debug_struct("Router").field("routes", &routes).field("fallback", &self.fallback.is_some()).finish()
+    }
+}
+
+impl<S: Clone + Send + Sync + 'static> Default for Router<S>
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl<S: Clone + Send + Sync + 'static> Router<S>
+{
+    pub fn new() -> Self
+    {
+        Self { routes: Vec::new(), fallback: None }
+    }
+    ///Обработчик `handler` запросов `method` по пути `path`, получает запрос и общее состояние сервера.
+    ///Паника если `*name` не последний сегмент пути
+    pub fn route<H, F>(mut self, method: Method, path: &str, handler: H) -> Self
+    where H: Fn(Request<Incoming>, S) -> F + Send + Sync + 'static,
+          F: Future + Send + 'static,
+          F::Output: IntoResponse
+    {
+        let segments = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| match s.chars().next()
+            {
+                Some(':') => Segment::Param(s[1..].to_owned()),
+                Some('*') => Segment::Tail(s[1..].to_owned()),
+                _ => Segment::Static(s.to_owned())
+            })
+            .collect::<Vec<_>>();
+        if let Some(i) = segments.iter().position(|s| matches!(s, Segment::Tail(_)))
+        {
+            assert!(i + 1 == segments.len(), "Остаток пути `*` должен быть последним сегментом маршрута `{}`", path);
+        }
+        self.routes.push(Route { method, segments, handler: boxed_handler(handler) });
+        self
+    }
+    pub fn get<H, F>(self, path: &str, handler: H) -> Self
+    where H: Fn(Request<Incoming>, S) -> F + Send + Sync + 'static, F: Future + Send + 'static, F::Output: IntoResponse
+    {
+        self.route(Method::GET, path, handler)
+    }
+    pub fn post<H, F>(self, path: &str, handler: H) -> Self
+    where H: Fn(Request<Incoming>, S) -> F + Send + Sync + 'static, F: Future + Send + 'static, F::Output: IntoResponse
+    {
+        self.route(Method::POST, path, handler)
+    }
+    pub fn put<H, F>(self, path: &str, handler: H) -> Self
+    where H: Fn(Request<Incoming>, S) -> F + Send + Sync + 'static, F: Future + Send + 'static, F::Output: IntoResponse
+    {
+        self.route(Method::PUT, path, handler)
+    }
+    pub fn patch<H, F>(self, path: &str, handler: H) -> Self
+    where H: Fn(Request<Incoming>, S) -> F + Send + Sync + 'static, F: Future + Send + 'static, F::Output: IntoResponse
+    {
+        self.route(Method::PATCH, path, handler)
+    }
+    pub fn delete<H, F>(self, path: &str, handler: H) -> Self
+    where H: Fn(Request<Incoming>, S) -> F + Send + Sync + 'static, F: Future + Send + 'static, F::Output: IntoResponse
+    {
+        self.route(Method::DELETE, path, handler)
+    }
+    ///обработчик запросов не подошедших ни к одному маршруту
+    pub fn fallback<H, F>(mut self, handler: H) -> Self
+    where H: Fn(Request<Incoming>, S) -> F + Send + Sync + 'static, F: Future + Send + 'static, F::Output: IntoResponse
+    {
+        self.fallback = Some(boxed_handler(handler));
+        self
+    }
+    fn dispatch(&self, mut req: Request<Incoming>, state: S) -> BoxFuture<'static, Response<BoxBody>>
+    {
+        let path = req.uri().path().to_owned();
+        let path = path.split('/').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+        let matched = self.routes
+            .iter()
+            .filter_map(|r| r.matches(&path).map(|p| (r, p)))
+            .collect::<Vec<_>>();
+        let route = matched
+            .iter()
+            .find(|(r, _)| r.method == req.method())
+            .or_else(|| matched.iter().find(|(r, _)| req.method() == Method::HEAD && r.method == Method::GET));
+        match (route, &self.fallback)
+        {
+            (Some((route, params)), _) =>
+            {
+                req.extensions_mut().insert(params.clone());
+                (route.handler)(req, state)
+            },
+            (None, _) if !matched.is_empty() =>
+            {
+                //HEAD обслуживается маршрутами GET
+                let mut allow: Vec<&str> = Vec::new();
+                for method in matched.iter().flat_map(|(r, _)| if r.method == Method::GET { vec!["GET", "HEAD"] } else { vec![r.method.as_str()] })
+                {
+                    if !allow.contains(&method)
+                    {
+                        allow.push(method);
+                    }
+                }
+                let mut response = error_response("Метод не поддерживается".to_owned(), StatusCode::METHOD_NOT_ALLOWED);
+                if let Ok(allow) = HeaderValue::from_str(&allow.join(", "))
+                {
+                    response.headers_mut().insert(ALLOW, allow);
+                }
+                Box::pin(async move { response })
+            },
+            (None, Some(fallback)) => fallback(req, state),
+            (None, None) => Box::pin(async { error_response("Не найдено".to_owned(), StatusCode::NOT_FOUND) })
+        }
+    }
+}
+
+fn boxed_handler<S, H, F>(handler: H) -> Handler<S>
+where H: Fn(Request<Incoming>, S) -> F + Send + Sync + 'static,
+      F: Future + Send + 'static,
+      F::Output: IntoResponse
+{
+    Arc::new(move |req, state|
+    {
+        let response = handler(req, state);
+        Box::pin(async move { response.await.into_response() })
+    })
+}
+
+///Сервис сервера после применения слоев tower-http
+#[derive(Clone)]
+struct ServerService(Arc<dyn Fn(Request<Incoming>) -> BoxFuture<'static, Response<BoxBody>> + Send + Sync>);
+
+impl ServerService
+{
+    fn from_service<T, B>(service: T) -> Self
+    where T: Service<Request<Incoming>, Response = Response<B>, Error = Infallible> + Clone + Send + Sync + 'static,
+          T::Future: Send + 'static,
+          B: Body<Data = Bytes, Error = hyper::Error> + Send + Sync + 'static
+    {
+        Self(Arc::new(move |req|
+        {
+            let mut service = service.clone();
+            Box::pin(async move
+            {
+                let ready = futures::future::poll_fn(|cx| service.poll_ready(cx)).await;
+                let response = match ready
+                {
+                    Ok(()) => service.call(req).await,
+                    Err(e) => Err(e)
+                };
+                match response
+                {
+                    Ok(response) => response.map(|b| b.boxed()),
+                    Err(never) => match never {}
+                }
+            })
+        }))
+    }
+}
+
+impl Service<Request<Incoming>> for ServerService
+{
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response<BoxBody>, Infallible>>;
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>>
+    {
+        Poll::Ready(Ok(()))
+    }
+    fn call(&mut self, req: Request<Incoming>) -> Self::Future
+    {
+        let response = (self.0)(req);
+        Box::pin(async move { Ok(response.await) })
+    }
+}
+
+///HTTP/1.1 и HTTP/2 (h2c) сервер с маршрутизацией `Router` и общим состоянием `S`,
+///доступным всем обработчикам (клонируется для каждого запроса, обычно `Arc` или пул подключений).
+///Адрес клиента доступен обработчикам в `Request::extensions` как `SocketAddr`
+///```ignore
+///let server = HttpServer::new(router, state)
+///    .with_cors(CorsLayer::permissive())
+///    .with_trace();
+///server.serve("0.0.0.0:8080", async { let _ = tokio::signal::ctrl_c().await; }).await?;
+///```
+pub struct HttpServer<S = ()>
+{
+    router: Arc<Router<S>>,
+    state: S,
+    cors: Option<CorsLayer>,
+    trace: bool,
+    shutdown_timeout: Duration
+}
+
+impl<S: Debug> Debug for HttpServer<S>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        f.debug_struct("HttpServer")
+            .field("router", &self.router)
+            .field("state", &self.state)
+            .field("cors", &self.cors)
+            .field("trace", &self.trace)
+            .field("shutdown_timeout", &self.shutdown_timeout)
+            .finish()
+    }
+}
+
+///Запущенный `HttpServer`, удаление не останавливает сервер
+#[derive(Debug)]
+pub struct ServerHandle
+{
+    addr: SocketAddr,
+    shutdown: Arc<Notify>,
+    task: JoinHandle<()>
+}
+
+impl<S: Clone + Send + Sync + 'static> HttpServer<S>
+{
+    pub fn new(router: Router<S>, state: S) -> Self
+    {
+        Self
+        {
+            router: Arc::new(router),
+            state,
+            cors: None,
+            trace: false,
+            shutdown_timeout: Duration::from_secs(30)
+        }
+    }
+    ///обработка CORS, в том числе ответы на предварительные запросы OPTIONS
+    pub fn with_cors(mut self, cors: CorsLayer) -> Self
+    {
+        self.cors = Some(cors);
+        self
+    }
+    ///span и события tracing для каждого запроса (`tower_http::trace::TraceLayer`)
+    pub fn with_trace(mut self) -> Self
+    {
+        self.trace = true;
+        self
+    }
+    ///время ожидания завершения выполняемых запросов при остановке, по умолчанию 30 секунд
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self
+    {
+        self.shutdown_timeout = timeout;
+        self
+    }
+    fn service(&self) -> ServerService
+    {
+        let (router, state) = (self.router.clone(), self.state.clone());
+        let mut service = ServerService(Arc::new(move |req| router.dispatch(req, state.clone())));
+        if let Some(cors) = &self.cors
+        {
+            service = ServerService::from_service(cors.layer(service));
+        }
+        if self.trace
+        {
+            service = ServerService::from_service(TraceLayer::new_for_http().layer(service));
+        }
+        service
+    }
+    ///Запуск сервера в фоне, порт 0 - свободный порт (адрес в `ServerHandle::addr`)
+    pub async fn bind<A: ToSocketAddrs>(self, addr: A) -> Result<ServerHandle, Error>
+    {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        let service = self.service();
+        let shutdown = Arc::new(Notify::new());
+        let signal = shutdown.clone();
+        let shutdown_timeout = self.shutdown_timeout;
+        tracing::info!("HTTP сервер запущен на {}", addr);
+        let task = tokio::spawn(async move
+        {
+            let graceful = GracefulShutdown::new();
+            let builder = auto::Builder::new(TokioExecutor::new());
+            loop
+            {
+                tokio::select!
+                {
+                    _ = signal.notified() => break,
+                    accepted = listener.accept() => match accepted
+                    {
+                        Ok((stream, remote)) =>
+                        {
+                            let service = service.clone();
+                            let service = hyper::service::service_fn(move |mut req: Request<Incoming>|
+                            {
+                                req.extensions_mut().insert(remote);
+                                service.clone().call(req)
+                            });
+                            let connection = graceful.watch(builder.serve_connection_with_upgrades(TokioIo::new(stream), service).into_owned());
+                            tokio::spawn(async move
+                            {
+                                if let Err(e) = connection.await
+                                {
+                                    tracing::debug!("Ошибка соединения с {}: {}", remote, e);
+                                }
+                            });
+                        },
+                        Err(e) =>
+                        {
+                            //например превышен лимит открытых файлов, пробуем позже
+                            tracing::error!("Ошибка приема соединения сервером {}: {}", addr, e);
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                        }
+                    }
+                }
+            }
+            drop(listener);
+            tracing::info!("Остановка HTTP сервера {}", addr);
+            if tokio::time::timeout(shutdown_timeout, graceful.shutdown()).await.is_err()
+            {
+                tracing::warn!("Не все запросы к серверу {} завершены за {:?}", addr, shutdown_timeout);
+            }
+        });
+        Ok(ServerHandle { addr, shutdown, task })
+    }
+    ///Работа сервера до завершения `shutdown`, после чего новые соединения не принимаются,
+    ///а выполняемые запросы завершаются в пределах `with_shutdown_timeout`
+    pub async fn serve<A: ToSocketAddrs, F: Future<Output = ()>>(self, addr: A, shutdown: F) -> Result<(), Error>
+    {
+        let handle = self.bind(addr).await?;
+        shutdown.await;
+        handle.shutdown().await;
+        Ok(())
+    }
+}
+
+impl ServerHandle
+{
+    pub fn addr(&self) -> SocketAddr
+    {
+        self.addr
+    }
+    ///Остановка приема соединений и ожидание завершения выполняемых запросов
+    pub async fn shutdown(self)
+    {
+        self.shutdown.notify_one();
+        if let Err(e) = self.task.await
+        {
+            tracing::error!("Ошибка остановки HTTP сервера {}: {}", self.addr, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{net::SocketAddr, sync::{atomic::{AtomicUsize, Ordering}, Arc}, time::Duration};
+    use hyper::{body::Incoming, header::{ACCESS_CONTROL_ALLOW_ORIGIN, ALLOW, ORIGIN}, Method, Request, StatusCode};
+    use serde::{Deserialize, Serialize};
+    use super::{CorsLayer, HttpServer, PathParams, Router};
+    use crate::{error::Error, http::{json_response, ok_response, HyperClient}};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Item
+    {
+        id: String,
+        name: String,
+        counter: usize
+    }
+
+    fn param(req: &Request<Incoming>, name: &str) -> String
+    {
+        req.extensions().get::<PathParams>().and_then(|p| p.get(name)).unwrap_or_default().to_owned()
+    }
+
+    fn client(addr: SocketAddr, path: &str) -> HyperClient
+    {
+        HyperClient::builder(format!("http://{}{}", addr, path)).with_retry_count(1).build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_server_routing()
+    {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let router = Router::new()
+            .get("/items/:id", |req, counter: Arc<AtomicUsize>| async move
+            {
+                let counter = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                json_response(&Item { id: param(&req, "id"), name: "товар".to_owned(), counter })
+            })
+            .delete("/items/:id", |_, _| async {})
+            .post("/items/:id/fail", |_, _| async { Err::<hyper::Response<_>, _>(Error::ConfigError("сбой".to_owned())) })
+            .get("/files/*path", |req, _| async move { ok_response(param(&req, "path")) })
+            .get("/remote", |req, _| async move { ok_response(req.extensions().get::<SocketAddr>().unwrap().ip().to_string()) });
+        let server = HttpServer::new(router, counter.clone()).with_trace().bind("127.0.0.1:0").await.unwrap();
+        let addr = server.addr();
+
+        let item: Item = client(addr, "/items/%D0%B0%201").get_json().await.unwrap();
+        assert_eq!(item, Item { id: "а 1".to_owned(), name: "товар".to_owned(), counter: 1 });
+        let deleted = client(addr, "/items/1").delete(&[] as &[(&str, &str)]).await.unwrap();
+        assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+        let head = client(addr, "/items/2").request(Method::HEAD, &[] as &[(&str, &str)], None::<()>).await.unwrap();
+        assert_eq!((head.status(), head.text()), (StatusCode::OK, String::new()));
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+
+        let not_allowed = client(addr, "/items/1").put_with_body(&()).await.unwrap();
+        assert_eq!(not_allowed.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(not_allowed.headers().get(ALLOW).unwrap(), "GET, HEAD, DELETE");
+        //текст ошибки остается в логе сервера
+        let failed = client(addr, "/items/1/fail").post_with_body(&()).await.unwrap();
+        assert_eq!(failed.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(failed.text(), "");
+        let not_found = client(addr, "/items").get().await.unwrap();
+        assert_eq!(not_found.status(), StatusCode::NOT_FOUND);
+        assert_eq!(client(addr, "/files/css/main.css").get().await.unwrap().text(), "css/main.css");
+        assert_eq!(client(addr, "/files").get().await.unwrap().text(), "");
+        assert_eq!(client(addr, "/remote").get().await.unwrap().text(), "127.0.0.1");
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_server_fallback_and_cors()
+    {
+        let router = Router::new()
+            .get("/", |_, _| async { ok_response("главная".to_owned()) })
+            .fallback(|req: Request<Incoming>, _| async move { ok_response(format!("нет {}", req.uri().path())) });
+        let server = HttpServer::new(router, ()).with_cors(CorsLayer::permissive()).bind("127.0.0.1:0").await.unwrap();
+        let addr = server.addr();
+        assert_eq!(client(addr, "/other").get().await.unwrap().text(), "нет /other");
+        let response = client(addr, "/").with_header(ORIGIN, "http://example.com").get().await.unwrap();
+        assert_eq!(response.text(), "главная");
+        assert_eq!(response.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "*");
+        let preflight = client(addr, "/")
+            .with_header(ORIGIN, "http://example.com")
+            .with_header(hyper::header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .request(Method::OPTIONS, &[] as &[(&str, &str)], None::<()>)
+            .await
+            .unwrap();
+        assert_eq!(preflight.status(), StatusCode::OK);
+        assert_eq!(preflight.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), "*");
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_server_graceful_shutdown()
+    {
+        let router = Router::new().get("/slow", |_, _| async
+        {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            ok_response("готово".to_owned())
+        });
+        let server = HttpServer::new(router, ()).with_shutdown_timeout(Duration::from_secs(5)).bind("127.0.0.1:0").await.unwrap();
+        let addr = server.addr();
+        let request = tokio::spawn(async move { client(addr, "/slow").get().await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        server.shutdown().await;
+        //начатый запрос завершается, новые соединения не принимаются
+        assert_eq!(request.await.unwrap().unwrap().text(), "готово");
+        assert!(client(addr, "/slow").get().await.is_err());
+    }
+}
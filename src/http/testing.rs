@@ -0,0 +1,420 @@
+//!Локальный HTTP сервер для тестов кода использующего `HyperClient` (фича `testing`)
+//!```ignore
+//!let server = MockServer::start().await;
+//!server.mock(Mock::new(Method::GET, "/api/items")
+//!    .with_query("page", "1")
+//!    .with_header(ACCEPT, "application/json")
+//!    .respond(MockResponse::new(StatusCode::SERVICE_UNAVAILABLE))
+//!    .respond(MockResponse::ok().with_json(&items).with_delay(Duration::from_millis(50)))
+//!    .expect(2));
+//!let client = HyperClient::builder(server.url("/api/items")).build()?;
+//!let items: Vec<Item> = client.get_json_with_params(&[("page", "1")]).await?;
+//!server.verify();
+//!```
+use std::{net::SocketAddr, sync::{Arc, Mutex}, time::Duration};
+use http_body_util::BodyExt;
+use hyper::{body::Bytes, header::{HeaderName, HeaderValue, CONTENT_TYPE}, HeaderMap, Method, Request, Response, StatusCode, Uri};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::task::{JoinHandle, JoinSet};
+use super::{to_body, BoxBody};
+
+///Ожидаемый запрос и ответы на него.
+///Запрос подходит если совпадают метод, путь, указанные параметры адреса, заголовки и тело,
+///ответы выдаются по очереди, последний повторяется для всех следующих запросов
+#[derive(Debug, Clone)]
+pub struct Mock
+{
+    method: Option<Method>,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(HeaderName, String)>,
+    body: Option<BodyMatcher>,
+    responses: Vec<MockResponse>,
+    expected: Option<usize>
+}
+
+#[derive(Debug, Clone)]
+enum BodyMatcher
+{
+    Bytes(Bytes),
+    ///сравнение разобранного JSON, порядок ключей и пробелы не важны
+    Json(serde_json::Value)
+}
+
+///Ответ сервера на запрос подошедший к `Mock`
+#[derive(Debug, Clone)]
+pub struct MockResponse
+{
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    delay: Option<Duration>
+}
+
+///Запрос полученный `MockServer`
+#[derive(Debug, Clone)]
+pub struct RecordedRequest
+{
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    ///номер подошедшего `Mock` в порядке добавления, None - запрос не подошел ни к одному
+    pub mock: Option<usize>
+}
+
+///Сервер на свободном порту 127.0.0.1, при удалении останавливается и закрывает открытые соединения
+pub struct MockServer
+{
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    accept: JoinHandle<()>
+}
+
+#[derive(Debug, Default)]
+struct State
+{
+    ///ожидания и количество подошедших к ним запросов
+    mocks: Vec<(Mock, usize)>,
+    requests: Vec<RecordedRequest>
+}
+
+impl Mock
+{
+    pub fn new<P: Into<String>>(method: Method, path: P) -> Self
+    {
+        Self { method: Some(method), ..Self::any(path) }
+    }
+    ///запрос с любым методом
+    pub fn any<P: Into<String>>(path: P) -> Self
+    {
+        Self
+        {
+            method: None,
+            path: path.into(),
+            query: Vec::new(),
+            headers: Vec::new(),
+            body: None,
+            responses: Vec::new(),
+            expected: None
+        }
+    }
+    ///параметр адреса, значение сравнивается после декодирования
+    pub fn with_query<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self
+    {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+    ///один из заголовков `name` запроса должен быть равен `value`
+    pub fn with_header<V: Into<String>>(mut self, name: HeaderName, value: V) -> Self
+    {
+        self.headers.push((name, value.into()));
+        self
+    }
+    pub fn with_body<B: Into<Bytes>>(mut self, body: B) -> Self
+    {
+        self.body = Some(BodyMatcher::Bytes(body.into()));
+        self
+    }
+    pub fn with_json_body<B: Serialize + ?Sized>(mut self, body: &B) -> Self
+    {
+        self.body = Some(BodyMatcher::Json(serde_json::to_value(body).expect("тело запроса не сериализуется в JSON")));
+        self
+    }
+    ///следующий ответ в очереди, без ответов отвечает 200 с пустым телом
+    pub fn respond(mut self, response: MockResponse) -> Self
+    {
+        self.responses.push(response);
+        self
+    }
+    ///ожидаемое количество запросов, проверяется в `MockServer::verify`
+    pub fn expect(mut self, times: usize) -> Self
+    {
+        self.expected = Some(times);
+        self
+    }
+    fn matches(&self, req: &RecordedRequest) -> bool
+    {
+        if self.method.as_ref().is_some_and(|m| m != req.method) || self.path != req.uri.path()
+        {
+            return false;
+        }
+        let query = url::form_urlencoded::parse(req.uri.query().unwrap_or_default().as_bytes()).into_owned().collect::<Vec<_>>();
+        if !self.query.iter().all(|q| query.contains(q))
+        {
+            return false;
+        }
+        if !self.headers.iter().all(|(name, value)| req.headers.get_all(name).iter().any(|v| v == value.as_str()))
+        {
+            return false;
+        }
+        match &self.body
+        {
+            None => true,
+            Some(BodyMatcher::Bytes(body)) => *body == req.body,
+            Some(BodyMatcher::Json(body)) => serde_json::from_slice::<serde_json::Value>(&req.body).is_ok_and(|b| b == *body)
+        }
+    }
+    fn describe(&self) -> String
+    {
+        let method = self.method.as_ref().map(|m| m.as_str()).unwrap_or("*");
+        format!("{} {}", method, self.path)
+    }
+}
+
+impl MockResponse
+{
+    pub fn new(status: StatusCode) -> Self
+    {
+        Self { status, headers: HeaderMap::new(), body: Bytes::new(), delay: None }
+    }
+    pub fn ok() -> Self
+    {
+        Self::new(StatusCode::OK)
+    }
+    ///заголовок с некорректным значением не добавляется, а ошибка пишется в лог
+    pub fn with_header<S: AsRef<str> + ToString>(mut self, name: HeaderName, value: S) -> Self
+    {
+        match HeaderValue::from_str(value.as_ref())
+        {
+            Ok(v) => { self.headers.append(name, v); },
+            Err(e) => tracing::error!("Некорректное значение заголовка {}: {}", name, e)
+        }
+        self
+    }
+    pub fn with_body<B: Into<Bytes>>(mut self, body: B) -> Self
+    {
+        self.body = body.into();
+        self
+    }
+    ///тело JSON с `Content-Type: application/json`
+    pub fn with_json<B: Serialize + ?Sized>(mut self, body: &B) -> Self
+    {
+        self.body = Bytes::from(serde_json::to_vec(body).expect("тело ответа не сериализуется в JSON"));
+        self.headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        self
+    }
+    ///задержка перед отправкой ответа
+    pub fn with_delay(mut self, delay: Duration) -> Self
+    {
+        self.delay = Some(delay);
+        self
+    }
+    fn to_response(&self) -> Response<BoxBody>
+    {
+        let mut response = Response::new(to_body(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+}
+
+impl RecordedRequest
+{
+    pub fn text(&self) -> String
+    {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error>
+    {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+impl MockServer
+{
+    ///запуск сервера HTTP/1.1 и HTTP/2 (h2c), запросы без подходящего `Mock` получают 404
+    pub async fn start() -> Self
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("не удалось открыть порт для MockServer");
+        let addr = listener.local_addr().expect("не удалось получить адрес MockServer");
+        let state = Arc::new(Mutex::new(State::default()));
+        let server_state = state.clone();
+        let accept = tokio::spawn(async move
+        {
+            //соединения прерываются вместе с задачей приема при удалении сервера
+            let mut connections = JoinSet::new();
+            while let Ok((stream, _)) = listener.accept().await
+            {
+                while connections.try_join_next().is_some() {}
+                let state = server_state.clone();
+                connections.spawn(async move
+                {
+                    let service = hyper::service::service_fn(move |req| Self::handle(state.clone(), req));
+                    let _ = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new()).serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        Self { addr, state, accept }
+    }
+    async fn handle(state: Arc<Mutex<State>>, req: Request<hyper::body::Incoming>) -> Result<Response<BoxBody>, hyper::Error>
+    {
+        let (parts, body) = req.into_parts();
+        let body = body.collect().await?.to_bytes();
+        let mut request = RecordedRequest { method: parts.method, uri: parts.uri, headers: parts.headers, body, mock: None };
+        let response = {
+            let mut state = state.lock().unwrap();
+            let matched = state.mocks.iter_mut().enumerate().find(|(_, (mock, _))| mock.matches(&request));
+            let response = matched.map(|(i, (mock, calls))|
+            {
+                request.mock = Some(i);
+                *calls += 1;
+                mock.responses.get(*calls - 1).or(mock.responses.last()).cloned().unwrap_or_else(MockResponse::ok)
+            });
+            tracing::debug!("MockServer получил запрос {} {}, ответ: {:?}", request.method, request.uri, response.as_ref().map(|r| r.status));
+            state.requests.push(request);
+            response
+        };
+        match response
+        {
+            Some(response) =>
+            {
+                if let Some(delay) = response.delay
+                {
+                    tokio::time::sleep(delay).await;
+                }
+                Ok(response.to_response())
+            },
+            None => Ok(super::error_response("Нет подходящего Mock".to_owned(), StatusCode::NOT_FOUND))
+        }
+    }
+    pub fn addr(&self) -> SocketAddr
+    {
+        self.addr
+    }
+    ///адрес сервера `http://127.0.0.1:порт`
+    pub fn uri(&self) -> String
+    {
+        format!("http://{}", self.addr)
+    }
+    ///адрес пути на сервере, `path` может содержать параметры
+    pub fn url(&self, path: &str) -> Uri
+    {
+        format!("http://{}/{}", self.addr, path.trim_start_matches('/')).parse().expect("некорректный путь для MockServer")
+    }
+    ///добавление ожидания, проверяются в порядке добавления
+    pub fn mock(&self, mock: Mock) -> &Self
+    {
+        self.state.lock().unwrap().mocks.push((mock, 0));
+        self
+    }
+    ///все полученные запросы в порядке получения
+    pub fn requests(&self) -> Vec<RecordedRequest>
+    {
+        self.state.lock().unwrap().requests.clone()
+    }
+    ///количество запросов подошедших к `Mock` с номером `mock` в порядке добавления
+    pub fn calls(&self, mock: usize) -> usize
+    {
+        self.state.lock().unwrap().mocks.get(mock).map(|m| m.1).unwrap_or_default()
+    }
+    ///удаление ожиданий и полученных запросов
+    pub fn reset(&self)
+    {
+        let mut state = self.state.lock().unwrap();
+        state.mocks.clear();
+        state.requests.clear();
+    }
+    ///Паника если количество запросов не совпало с `Mock::expect` или были запросы без подходящего `Mock`
+    pub fn verify(&self)
+    {
+        //блокировка снимается до паники, чтобы сервером можно было пользоваться дальше
+        let errors = {
+            let state = self.state.lock().unwrap();
+            let mut errors = state.mocks
+                .iter()
+                .filter(|(mock, calls)| mock.expected.is_some_and(|e| e != *calls))
+                .map(|(mock, calls)| format!("{}: ожидалось запросов {}, получено {}", mock.describe(), mock.expected.unwrap_or_default(), calls))
+                .collect::<Vec<_>>();
+            errors.extend(state.requests.iter().filter(|r| r.mock.is_none()).map(|r| format!("{} {}: нет подходящего Mock", r.method, r.uri)));
+            errors
+        };
+        if !errors.is_empty()
+        {
+            panic!("MockServer {}:\n{}", self.addr, errors.join("\n"));
+        }
+    }
+}
+
+impl Drop for MockServer
+{
+    fn drop(&mut self)
+    {
+        self.accept.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::time::Duration;
+    use hyper::{header::{ACCEPT, CONTENT_TYPE}, Method, StatusCode};
+    use serde::{Deserialize, Serialize};
+    use super::{Mock, MockResponse, MockServer};
+    use crate::http::HyperClient;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Item
+    {
+        id: u32,
+        name: String
+    }
+
+    #[tokio::test]
+    async fn test_mock_server_matching()
+    {
+        let server = MockServer::start().await;
+        let item = Item { id: 1, name: "первый".to_owned() };
+        server
+            .mock(Mock::new(Method::GET, "/items").with_query("name", "первый").with_header(ACCEPT, "application/json").respond(MockResponse::ok().with_json(&[&item])).expect(1))
+            .mock(Mock::new(Method::POST, "/items").with_json_body(&item).respond(MockResponse::new(StatusCode::CREATED).with_header(CONTENT_TYPE, "text/plain").with_body("создан")))
+            .mock(Mock::any("/any").respond(MockResponse::new(StatusCode::NO_CONTENT)));
+        let client = HyperClient::builder(server.url("/items")).with_retry_count(1).build().unwrap();
+        let items: Vec<Item> = client.get_json_with_params(&[("name", "первый")]).await.unwrap();
+        assert_eq!(items, vec![Item { id: 1, name: "первый".to_owned() }]);
+        let created = client.post_with_body(&item).await.unwrap();
+        assert_eq!((created.status(), created.text()), (StatusCode::CREATED, "создан".to_owned()));
+        let deleted = HyperClient::builder(server.url("/any")).with_retry_count(1).build().unwrap().delete(&[] as &[(&str, &str)]).await.unwrap();
+        assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+        server.verify();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].method, Method::POST);
+        assert_eq!(requests[1].json::<Item>().unwrap(), item);
+        assert_eq!((requests[0].mock, requests[1].mock, requests[2].mock), (Some(0), Some(1), Some(2)));
+        //запрос без подходящего Mock получает 404 и не проходит проверку
+        let other = client.get_with_params(&[("name", "второй")]).await.unwrap();
+        assert_eq!(other.status(), StatusCode::NOT_FOUND);
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| server.verify())).is_err());
+        server.reset();
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mock_server_sequence_and_delay()
+    {
+        let server = MockServer::start().await;
+        server.mock(Mock::new(Method::GET, "/flaky")
+            .respond(MockResponse::new(StatusCode::SERVICE_UNAVAILABLE))
+            .respond(MockResponse::ok().with_body("медленно").with_delay(Duration::from_millis(300)))
+            .respond(MockResponse::ok().with_body("готово"))
+            .expect(4));
+        let client = HyperClient::builder(server.url("/flaky"))
+            .with_retry_count(3)
+            .with_backoff(Duration::from_millis(10), Duration::from_millis(10))
+            .with_read_timeout(Duration::from_millis(150))
+            .build()
+            .unwrap();
+        //503, таймаут медленного ответа, затем успешный ответ
+        assert_eq!(client.get().await.unwrap().text(), "готово");
+        //последний ответ повторяется
+        assert_eq!(client.get().await.unwrap().text(), "готово");
+        assert_eq!(server.calls(0), 4);
+        server.verify();
+        drop(server);
+        //после удаления сервер не отвечает и в уже открытом соединении клиента
+        assert!(client.get().await.is_err());
+    }
+}
@@ -0,0 +1,362 @@
+use std::sync::Arc;
+use rustls::{
+    client::{danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier}, WebPkiServerVerifier},
+    crypto::CryptoProvider,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    sign::CertifiedKey,
+    CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme
+};
+use crate::error::Error;
+
+///Настройки TLS клиента, задаются через `HyperClientBuilder`
+#[derive(Debug, Clone)]
+pub(crate) struct TlsOptions
+{
+    native_roots: bool,
+    roots: Vec<CertificateDer<'static>>,
+    client_auth: Option<(Vec<CertificateDer<'static>>, Arc<PrivateKeyDer<'static>>)>,
+    pins: Vec<[u8; 32]>,
+    accept_invalid_certs: bool
+}
+
+impl Default for TlsOptions
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            native_roots: true,
+            roots: Vec::new(),
+            client_auth: None,
+            pins: Vec::new(),
+            accept_invalid_certs: false
+        }
+    }
+}
+
+impl TlsOptions
+{
+    pub(crate) fn set_native_roots(&mut self, enabled: bool)
+    {
+        self.native_roots = enabled;
+    }
+    pub(crate) fn add_roots_pem(&mut self, pem: &[u8]) -> Result<(), String>
+    {
+        let certs = parse_certs(pem)?;
+        self.roots.extend(certs);
+        Ok(())
+    }
+    ///сертификат клиента проверяется на соответствие закрытому ключу
+    pub(crate) fn set_client_auth_pem(&mut self, cert: &[u8], key: &[u8]) -> Result<(), String>
+    {
+        let certs = parse_certs(cert)?;
+        let key = PrivateKeyDer::from_pem_slice(key).map_err(|e| format!("закрытый ключ клиента: {}", e))?;
+        let signer = rustls::crypto::ring::sign::any_supported_type(&key).map_err(|e| format!("закрытый ключ клиента: {}", e))?;
+        match CertifiedKey::new(certs.clone(), signer).keys_match()
+        {
+            Ok(()) | Err(rustls::Error::InconsistentKeys(rustls::InconsistentKeys::Unknown)) => (),
+            Err(e) => return Err(format!("сертификат клиента не соответствует ключу: {}", e))
+        }
+        self.client_auth = Some((certs, Arc::new(key)));
+        Ok(())
+    }
+    pub(crate) fn add_pin(&mut self, spki_sha256: [u8; 32])
+    {
+        self.pins.push(spki_sha256);
+    }
+    pub(crate) fn set_accept_invalid_certs(&mut self, accept: bool)
+    {
+        self.accept_invalid_certs = accept;
+    }
+    ///ошибка если проверить сертификат сервера будет нечем
+    pub(crate) fn validate(&self) -> Result<(), String>
+    {
+        if !self.native_roots && self.roots.is_empty() && !self.accept_invalid_certs
+        {
+            return Err("системные корневые сертификаты отключены, а свои не заданы".to_owned());
+        }
+        Ok(())
+    }
+    pub(crate) fn client_config(&self) -> Result<ClientConfig, Error>
+    {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+        let builder = if self.accept_invalid_certs || !self.pins.is_empty()
+        {
+            let inner = if self.accept_invalid_certs
+            {
+                tracing::warn!("Проверка сертификатов сервера отключена");
+                None
+            }
+            else
+            {
+                let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(self.root_store()?), provider.clone())
+                    .build()
+                    .map_err(|e| Error::ConfigError(e.to_string()))?;
+                Some(verifier)
+            };
+            builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinnedVerifier { inner, pins: self.pins.clone(), provider }))
+        }
+        else
+        {
+            builder.with_root_certificates(self.root_store()?)
+        };
+        let config = match &self.client_auth
+        {
+            Some((certs, key)) => builder.with_client_auth_cert(certs.clone(), key.clone_key())?,
+            None => builder.with_no_client_auth()
+        };
+        Ok(config)
+    }
+    fn root_store(&self) -> Result<RootCertStore, Error>
+    {
+        let mut store = RootCertStore::empty();
+        if self.native_roots
+        {
+            let native = rustls_native_certs::load_native_certs();
+            for e in &native.errors
+            {
+                tracing::warn!("Ошибка загрузки системного сертификата: {}", e);
+            }
+            let (added, ignored) = store.add_parsable_certificates(native.certs);
+            tracing::debug!("Загружено {} системных корневых сертификатов, пропущено {}", added, ignored);
+        }
+        for cert in &self.roots
+        {
+            store.add(cert.clone())?;
+        }
+        if store.is_empty()
+        {
+            return Err(std::io::Error::other("не найдено ни одного корневого сертификата").into());
+        }
+        Ok(store)
+    }
+}
+
+///Хэш sha256 SubjectPublicKeyInfo первого сертификата из PEM, значение для `HyperClientBuilder::with_pinned_spki_sha256`
+pub fn spki_sha256(cert_pem: &[u8]) -> Result<[u8; 32], Error>
+{
+    let cert = parse_certs(cert_pem).map_err(Error::ConfigError)?.remove(0);
+    spki_hash(&cert).map_err(Error::from)
+}
+
+fn spki_hash(cert: &CertificateDer<'_>) -> Result<[u8; 32], rustls::Error>
+{
+    let parsed = webpki::EndEntityCert::try_from(cert).map_err(|_| rustls::Error::InvalidCertificate(CertificateError::BadEncoding))?;
+    let digest = ring::digest::digest(&ring::digest::SHA256, parsed.subject_public_key_info().as_ref());
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(digest.as_ref());
+    Ok(hash)
+}
+
+fn parse_certs(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, String>
+{
+    let certs = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("сертификат PEM: {}", e))?;
+    if certs.is_empty()
+    {
+        return Err("в PEM не найдено ни одного сертификата".to_owned());
+    }
+    Ok(certs)
+}
+
+///Проверка сертификата сервера с привязкой к ключу,
+///`inner` - обычная проверка цепочки (None если проверка отключена), затем SPKI одного из сертификатов цепочки должен совпасть с `pins`
+#[derive(Debug)]
+struct PinnedVerifier
+{
+    inner: Option<Arc<WebPkiServerVerifier>>,
+    pins: Vec<[u8; 32]>,
+    provider: Arc<CryptoProvider>
+}
+
+impl ServerCertVerifier for PinnedVerifier
+{
+    fn verify_server_cert(&self, end_entity: &CertificateDer<'_>, intermediates: &[CertificateDer<'_>], server_name: &ServerName<'_>, ocsp_response: &[u8], now: UnixTime) -> Result<ServerCertVerified, rustls::Error>
+    {
+        if let Some(inner) = &self.inner
+        {
+            inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        }
+        if !self.pins.is_empty()
+        {
+            let mut pinned = false;
+            for cert in std::iter::once(end_entity).chain(intermediates)
+            {
+                if self.pins.contains(&spki_hash(cert)?)
+                {
+                    pinned = true;
+                    break;
+                }
+            }
+            if !pinned
+            {
+                tracing::error!("Ключ сертификата сервера {:?} не совпадает ни с одним закрепленным", server_name);
+                return Err(rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure));
+            }
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error>
+    {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error>
+    {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme>
+    {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests
+{
+    use std::{net::SocketAddr, sync::Arc};
+    use hyper::{body::Bytes, Request};
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use rustls::{pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer}, server::WebPkiClientVerifier, RootCertStore, ServerConfig};
+    use crate::{error::Error, http::{to_body, HttpVersionPolicy, HyperClient, HyperClientBuilder}};
+
+    pub(crate) const CA: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/certs/ca.pem"));
+    const SERVER_CERT: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/certs/server.pem"));
+    const SERVER_KEY: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/certs/server.key"));
+    const CLIENT_CERT: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/certs/client.pem"));
+    const CLIENT_KEY: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/certs/client.key"));
+
+    ///https сервер с сертификатом выданным тестовым CA, поддерживает HTTP/1.1 и HTTP/2 через ALPN,
+    ///отвечает `client-cert` если клиент предъявил сертификат
+    pub(crate) async fn tls_server(client_auth: bool) -> SocketAddr
+    {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let certs = CertificateDer::pem_slice_iter(SERVER_CERT).collect::<Result<Vec<_>, _>>().unwrap();
+        let key = PrivateKeyDer::from_pem_slice(SERVER_KEY).unwrap();
+        let builder = ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions().unwrap();
+        let builder = if client_auth
+        {
+            let mut roots = RootCertStore::empty();
+            roots.add(CertificateDer::from_pem_slice(CA).unwrap()).unwrap();
+            builder.with_client_cert_verifier(WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build().unwrap())
+        }
+        else
+        {
+            builder.with_no_client_auth()
+        };
+        let mut config = builder.with_single_cert(certs, key).unwrap();
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move
+        {
+            loop
+            {
+                let (stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move
+                {
+                    let stream = match acceptor.accept(stream).await
+                    {
+                        Ok(s) => s,
+                        Err(_) => return
+                    };
+                    let with_cert = stream.get_ref().1.peer_certificates().is_some_and(|c| !c.is_empty());
+                    let service = hyper::service::service_fn(move |_req: Request<hyper::body::Incoming>| async move
+                    {
+                        let body = if with_cert { "client-cert" } else { "ok" };
+                        Ok::<_, hyper::Error>(hyper::Response::new(to_body(Bytes::from(body))))
+                    });
+                    let _ = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new()).serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+        addr
+    }
+
+    fn builder(addr: SocketAddr) -> HyperClientBuilder
+    {
+        HyperClient::builder(format!("https://127.0.0.1:{}/", addr.port())).with_retry_count(1)
+    }
+
+    #[tokio::test]
+    async fn test_tls_root_certificates()
+    {
+        let addr = tls_server(false).await;
+        let result = builder(addr).build().unwrap().get().await;
+        assert!(result.is_err(), "сертификат неизвестного CA принят: {:?}", result);
+        let response = builder(addr).with_root_certificates_pem(CA).build().unwrap().get().await.unwrap();
+        assert_eq!(response.text(), "ok");
+        let response = builder(addr).with_native_roots(false).with_root_certificates_pem(CA).build().unwrap().get().await.unwrap();
+        assert_eq!(response.text(), "ok");
+        let response = builder(addr).with_native_roots(false).danger_accept_invalid_certs(true).build().unwrap().get().await.unwrap();
+        assert_eq!(response.text(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_tls_pinning()
+    {
+        let addr = tls_server(false).await;
+        let server_pin = super::spki_sha256(SERVER_CERT).unwrap();
+        let client_pin = super::spki_sha256(CLIENT_CERT).unwrap();
+        assert_ne!(server_pin, client_pin);
+        let response = builder(addr).with_root_certificates_pem(CA).with_pinned_spki_sha256(server_pin).build().unwrap().get().await.unwrap();
+        assert_eq!(response.text(), "ok");
+        let result = builder(addr).with_root_certificates_pem(CA).with_pinned_spki_sha256(client_pin).build().unwrap().get().await;
+        assert!(result.is_err());
+        //закрепление ключа работает и без проверки цепочки
+        let response = builder(addr).danger_accept_invalid_certs(true).with_pinned_spki_sha256(server_pin).build().unwrap().get().await.unwrap();
+        assert_eq!(response.text(), "ok");
+        let result = builder(addr).danger_accept_invalid_certs(true).with_pinned_spki_sha256(client_pin).build().unwrap().get().await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tls_client_certificate()
+    {
+        let addr = tls_server(true).await;
+        let result = builder(addr).with_root_certificates_pem(CA).build().unwrap().get().await;
+        assert!(result.is_err(), "запрос без сертификата клиента принят: {:?}", result);
+        let response = builder(addr)
+            .with_root_certificates_pem(CA)
+            .with_client_certificate_pem(CLIENT_CERT, CLIENT_KEY)
+            .build()
+            .unwrap()
+            .get().await.unwrap();
+        assert_eq!(response.text(), "client-cert");
+    }
+
+    #[tokio::test]
+    async fn test_tls_alpn_http2()
+    {
+        let addr = tls_server(false).await;
+        let response = builder(addr).with_root_certificates_pem(CA).build().unwrap().get().await.unwrap();
+        assert_eq!(response.version(), hyper::Version::HTTP_2);
+        let response = builder(addr)
+            .with_root_certificates_pem(CA)
+            .with_http_version(HttpVersionPolicy::Http1Only)
+            .build()
+            .unwrap()
+            .get().await.unwrap();
+        assert_eq!(response.version(), hyper::Version::HTTP_11);
+    }
+
+    #[test]
+    fn test_tls_builder_validation()
+    {
+        let error = |b: HyperClientBuilder| match b.build()
+        {
+            Err(Error::ConfigError(e)) => e,
+            other => panic!("ожидалась ошибка настройки, получено {:?}", other)
+        };
+        let builder = || HyperClient::builder("https://example.com/");
+        assert!(error(builder().with_root_certificates_pem("не сертификат")).contains("PEM"));
+        assert!(error(builder().with_client_certificate_pem(CLIENT_CERT, SERVER_KEY)).contains("не соответствует"));
+        assert!(error(builder().with_native_roots(false)).contains("корневые"));
+        assert!(builder().with_client_certificate_pem(CLIENT_CERT, CLIENT_KEY).build().is_ok());
+    }
+}
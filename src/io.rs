@@ -4,6 +4,8 @@ use logger::error;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 #[cfg(feature="async-io")]
 use futures::FutureExt;
+#[cfg(feature="encoding")]
+use crate::error::Error;
 ///чтение файла в бинарный вектор
 pub fn read_file_to_binary<P: AsRef<Path>>(file_path: P) -> std::io::Result<Vec<u8>>
 {
@@ -22,6 +24,13 @@ pub async fn read_file_to_binary_async<P: AsRef<Path>>(file_path: P) -> std::io:
     let _ = f.read_to_end(&mut buffer).await?;
     Ok(buffer)
 }
+///путь временного файла для загрузки `path`: `file.pdf` -> `file.pdf.part`
+pub fn part_file_path<P: AsRef<Path>>(path: P) -> PathBuf
+{
+    let mut name = path.as_ref().as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
 ///совпадение имени файла по маске
 /// ```
 /// coincidence_by_mask("file.txt",  "f*.txt")
@@ -227,7 +236,7 @@ mod tests
     {
         let _ = logger::StructLogger::new_default();
         let file = "/hard/xar/projects/test_data/copy_from_in_test_data/in3/15943916/envelope.ltr";
-        let file = super::open_file(file, None).await;
+        let file = super::open_file_with_encoding(file, None).await;
         info!("{}", file.unwrap());
     }
     #[tokio::test]
@@ -236,7 +245,7 @@ mod tests
     {
         let _ = logger::StructLogger::new_default();
         let file = "/hard/xar/projects/test_data/copy_from_in_test_data/in3/15943916/document.xml";
-        let file = super::open_file(file, None).await;
+        let file = super::open_file_with_encoding(file, None).await;
         info!("{}", file.unwrap());
     }
     #[test]